@@ -0,0 +1,58 @@
+// Writing every tick of a 1,000 member, 10,000 tick run straight to the files
+// against writing it through a BufWriter. Run with `cargo bench --bench
+// output`; each run leaves about 200 MB in the temporary directory until it
+// finishes.
+extern crate bubbles;
+
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::time::{Duration, Instant};
+
+use bubbles::{simulate_observed, SimulationConfig, TickState};
+
+fn seconds(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 * 1e-9
+}
+
+// The opinion and network rows the binary writes for a tick, laid out as it
+// lays them out by default.
+fn write_tick<W: Write>(opinions: &mut W, network: &mut W, state: &TickState) -> io::Result<()> {
+    for (index, opinion) in state.opinions.iter().enumerate() {
+        writeln!(opinions, "{}, {}, {}", state.tick, index, opinion[0])?;
+    }
+    for record in state.records {
+        writeln!(network, "{}, {}, {}, {}", record.tick, record.sender,
+                 record.recipient, record.weight)?;
+    }
+    Ok(())
+}
+
+// Time a whole run with both files opened through `wrap`.
+fn run<W: Write, F: Fn(File) -> W>(wrap: F) -> f64 {
+    let mut config = SimulationConfig::new(1000, 10, 0.1, 20.0, 60.0, 1);
+    config.ticks = 10000;
+    let paths = [env::temp_dir().join("bubbles_bench_opinions.csv"),
+                 env::temp_dir().join("bubbles_bench_network.csv")];
+    let mut opinions = wrap(File::create(&paths[0]).unwrap());
+    let mut network = wrap(File::create(&paths[1]).unwrap());
+
+    let start = Instant::now();
+    simulate_observed(&config, |state| write_tick(&mut opinions, &mut network, state).unwrap());
+    opinions.flush().unwrap();
+    network.flush().unwrap();
+    let elapsed = seconds(start.elapsed());
+
+    for path in &paths {
+        fs::remove_file(path).unwrap();
+    }
+    elapsed
+}
+
+fn main() {
+    println!("no output   {:>8.3} s", run(|_| io::sink()));
+    println!("unbuffered  {:>8.3} s", run(|file| file));
+    println!("buffered    {:>8.3} s", run(BufWriter::new));
+}
@@ -0,0 +1,81 @@
+// Updating weights in a dense 2,000 member matrix with a separate get and put
+// against a single replace, and through Network::set_weight, which uses
+// replace. Run with `cargo bench --bench replace`.
+extern crate bubbles;
+extern crate rand;
+
+use std::time::{Duration, Instant};
+
+use bubbles::{Adjacency, Matrix, Network};
+use rand::{Rng, SeedableRng, StdRng};
+
+const POPULATION: usize = 2000;
+const PAIRS: usize = 1000000;
+const ROUNDS: usize = 20;
+
+fn seconds(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 * 1e-9
+}
+
+// The pairs every loop updates, in the same order. None of them is a
+// self-loop.
+fn pairs() -> Vec<(usize, usize)> {
+    let mut rng: StdRng = SeedableRng::from_seed(&[1][..]);
+    (0..PAIRS)
+        .map(|_| {
+            let row = rng.gen_range(0, POPULATION);
+            (row, (row + rng.gen_range(1, POPULATION)) % POPULATION)
+        })
+        .collect()
+}
+
+// Every pair already holds a weight and keeps one, so the network's neighbour
+// lists never change and only the storage is timed.
+fn weight(round: usize, index: usize) -> f64 {
+    0.25 + ((round + index) % 3) as f64 * 0.25
+}
+
+// Time ROUNDS passes over `pairs`, each handing the pair and its new weight to
+// `update`. Summing what it returns keeps the loop from being optimised away.
+fn time<F: FnMut(usize, usize, f64) -> f64>(pairs: &[(usize, usize)], mut update: F) -> f64 {
+    for &(row, col) in pairs {
+        update(row, col, 0.5);
+    }
+    let start = Instant::now();
+    let mut total = 0.0;
+    for round in 0..ROUNDS {
+        for (index, &(row, col)) in pairs.iter().enumerate() {
+            total += update(row, col, weight(round, index));
+        }
+    }
+    let elapsed = seconds(start.elapsed());
+    assert!(total > 0.0);
+    elapsed
+}
+
+fn main() {
+    let pairs = pairs();
+    let updates = (ROUNDS * PAIRS) as f64;
+
+    let mut matrix: Matrix<f64> = Matrix::new(POPULATION);
+    let get_put = time(&pairs, |row, col, weight| {
+        let old = Adjacency::get(&matrix, row, col);
+        Adjacency::put(&mut matrix, row, col, weight);
+        old
+    });
+    let mut matrix: Matrix<f64> = Matrix::new(POPULATION);
+    let replace = time(&pairs, |row, col, weight| {
+        Adjacency::replace(&mut matrix, row, col, weight)
+    });
+    let mut network: Network = Matrix::new(POPULATION).into();
+    let set_weight = time(&pairs, |row, col, weight| {
+        network.set_weight(row, col, weight);
+        weight
+    });
+
+    for &(name, elapsed) in &[("get then put", get_put), ("replace", replace),
+                              ("set_weight", set_weight)] {
+        println!("{:<13} {:>7.3} s  {:>6.2} ns per update", name, elapsed,
+                 elapsed / updates * 1e9);
+    }
+}
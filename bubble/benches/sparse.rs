@@ -0,0 +1,49 @@
+// Dense against sparse storage for a population of 10,000. Run with
+// `cargo bench --bench sparse`; the dense run alone needs about 800 MB.
+extern crate bubbles;
+
+use std::mem;
+use std::time::{Duration, Instant};
+
+use bubbles::{simulate_observed, SimulationConfig};
+
+const POPULATION: usize = 10000;
+const TICKS: usize = 1000;
+
+fn seconds(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 * 1e-9
+}
+
+// Time building the network and everything else before the first tick, then
+// the ticks themselves, and count the bytes holding the weights. A dense
+// matrix keeps every pair; sparse rows keep a key and a weight per tie in each
+// direction, before the maps' own bookkeeping.
+fn bench(sparse: bool) {
+    let mut config = SimulationConfig::new(POPULATION, 10, 0.1, 20.0, 60.0, 1);
+    config.sparse = sparse;
+    config.ticks = TICKS;
+
+    let start = Instant::now();
+    let mut first = None;
+    let result = simulate_observed(&config, |_| {
+        if first.is_none() {
+            first = Some(Instant::now());
+        }
+    });
+    let first = first.unwrap();
+    let ticks = seconds(first.elapsed());
+
+    let bytes = if sparse {
+        result.network.edges().len() * 2 * (mem::size_of::<usize>() + mem::size_of::<f64>())
+    } else {
+        POPULATION * POPULATION * mem::size_of::<f64>()
+    };
+    println!("{:<6} weights {:>9.1} MB  setup {:>7.3} s  per tick {:>9.3} ms",
+             if sparse { "sparse" } else { "dense" }, bytes as f64 / 1e6,
+             seconds(first.duration_since(start)), ticks / TICKS as f64 * 1e3);
+}
+
+fn main() {
+    bench(true);
+    bench(false);
+}
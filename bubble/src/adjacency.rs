@@ -0,0 +1,516 @@
+use rand::Rng;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, VecDeque};
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader};
+use std::str::FromStr;
+
+/// A member's place in the unit square, for spatial networks.
+pub type Position = (f64, f64);
+
+/// Scatter `n` members uniformly over the unit square.
+pub fn unit_square_positions<R: Rng>(n: usize, rng: &mut R) -> Vec<Position> {
+    (0..n).map(|_| (rng.next_f64(), rng.next_f64())).collect()
+}
+
+/// The block each member of a stochastic block model belongs to, with the
+/// blocks laid out one after another in member order.
+pub fn block_assignment(sizes: &[usize]) -> Vec<usize> {
+    sizes.iter().enumerate()
+        .flat_map(|(block, &size)| (0..size).map(move |_| block))
+        .collect()
+}
+
+// A member waiting to be settled by Dijkstra's algorithm, ordered so that the
+// cheapest comes out of a `BinaryHeap` first.
+#[derive(PartialEq)]
+struct Candidate {
+    cost: f64,
+    node: usize
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.partial_cmp(&self.cost).unwrap_or(Ordering::Equal)
+    }
+}
+
+// The cheapest cost from `source` to every member over `lists` of
+// `(neighbor, cost)`, or `None` for members it cannot reach.
+fn cheapest_paths(lists: &[Vec<(usize, f64)>], source: usize) -> Vec<Option<f64>> {
+    let mut costs: Vec<Option<f64>> = vec![None; lists.len()];
+    let mut heap = BinaryHeap::new();
+    costs[source] = Some(0.0);
+    heap.push(Candidate { cost: 0.0, node: source });
+    while let Some(Candidate { cost, node }) = heap.pop() {
+        if costs[node].is_some_and(|best| cost > best) {
+            continue;
+        }
+        for &(neighbor, step) in &lists[node] {
+            let next = cost + step;
+            if costs[neighbor].is_none_or(|best| next < best) {
+                costs[neighbor] = Some(next);
+                heap.push(Candidate { cost: next, node: neighbor });
+            }
+        }
+    }
+    costs
+}
+
+/// Storage for a square adjacency structure. A cell holding `T::default()`
+/// means the two members are not connected. Storage is symmetric unless it
+/// says otherwise, and the generators assume it is.
+///
+/// The network generators are provided on top of `new`, `get` and `put`, so
+/// every storage can be built with any topology.
+pub trait Adjacency<T> where T: Default + Copy + PartialEq {
+    fn new(size: usize) -> Self where Self: Sized;
+
+    fn size(&self) -> usize;
+
+    fn get(&self, row: usize, col: usize) -> T;
+
+    fn put(&mut self, row: usize, col: usize, value: T);
+
+    /// `put` that returns the value it overwrote. Dense storage overrides
+    /// this to look the cell up once instead of once per call.
+    fn replace(&mut self, row: usize, col: usize, value: T) -> T {
+        let old = self.get(row, col);
+        self.put(row, col, value);
+        old
+    }
+
+    /// The connected members of `node` and their weights, in ascending order.
+    fn neighbors(&self, node: usize) -> Vec<(usize, T)>;
+
+    /// The members with a relationship towards `node` and their weights, in
+    /// ascending order. The same as `neighbors` for symmetric storage.
+    fn incoming(&self, node: usize) -> Vec<(usize, T)> {
+        self.neighbors(node)
+    }
+
+    /// Whether every relationship has the same weight in both directions.
+    /// Storage that writes both cells itself always is; this catches code that
+    /// breaks the invariant the undirected measures rely on.
+    fn is_symmetric(&self) -> bool {
+        (0..self.size()).all(|node| {
+            self.neighbors(node).into_iter().all(|(other, weight)| self.get(other, node) == weight)
+        })
+    }
+
+    /// Every member's neighbours, as returned by `neighbors`, built in one
+    /// pass so that repeated lookups need not rescan the storage.
+    fn to_adjacency_list(&self) -> Vec<Vec<(usize, T)>> {
+        (0..self.size()).map(|node| self.neighbors(node)).collect()
+    }
+
+    /// The number of connected pairs, counting each pair once.
+    fn edge_count(&self) -> usize {
+        (0..self.size())
+            .map(|node| self.neighbors(node).iter().filter(|&&(other, _)| other > node).count())
+            .sum()
+    }
+
+    /// The fraction of possible pairs that are connected. Zero for fewer than
+    /// two members.
+    fn density(&self) -> f64 {
+        let n = self.size() as f64;
+        if n < 2.0 {
+            return 0.0;
+        }
+        self.edge_count() as f64 / (n * (n - 1.0) / 2.0)
+    }
+
+    /// The groups of members that can reach each other, ignoring the
+    /// direction of relationships. Members are listed in ascending order and
+    /// groups by their first member, so an isolated member forms its own
+    /// group.
+    fn connected_components(&self) -> Vec<Vec<usize>> {
+        let mut seen = vec![false; self.size()];
+        let mut components = Vec::new();
+        for start in 0..self.size() {
+            if seen[start] {
+                continue;
+            }
+            seen[start] = true;
+            let mut component = vec![start];
+            let mut index = 0;
+            while index < component.len() {
+                let node = component[index];
+                for (neighbor, _) in self.neighbors(node).into_iter().chain(self.incoming(node)) {
+                    if !seen[neighbor] {
+                        seen[neighbor] = true;
+                        component.push(neighbor);
+                    }
+                }
+                index += 1;
+            }
+            component.sort();
+            components.push(component);
+        }
+        components
+    }
+
+    /// The global clustering coefficient (transitivity): the fraction of
+    /// connected triplets whose ends are also connected. Zero when there are
+    /// no triplets at all.
+    fn clustering_coefficient(&self) -> f64 {
+        let mut closed = 0;
+        let mut triplets = 0;
+        for node in 0..self.size() {
+            let neighbors = self.neighbors(node);
+            for (index, &(first, _)) in neighbors.iter().enumerate() {
+                for &(second, _) in &neighbors[(index + 1)..] {
+                    triplets += 1;
+                    if self.get(first, second) != T::default() {
+                        closed += 1;
+                    }
+                }
+            }
+        }
+
+        if triplets == 0 {
+            return 0.0;
+        }
+        closed as f64 / triplets as f64
+    }
+
+    /// The mean shortest path length, in hops, over every pair of members
+    /// that can reach each other. Pairs in different components are skipped,
+    /// so a fragmented network reports the typical distance within its
+    /// components. Zero when no pair is connected.
+    fn average_path_length(&self) -> f64 {
+        let n = self.size();
+        let lists: Vec<Vec<usize>> = self.to_adjacency_list().into_iter()
+            .map(|list| list.into_iter().map(|(neighbor, _)| neighbor).collect())
+            .collect();
+
+        let mut total = 0;
+        let mut pairs = 0;
+        for source in 0..n {
+            // Breadth-first search outwards from the source.
+            let mut distances: Vec<Option<usize>> = vec![None; n];
+            let mut queue = VecDeque::new();
+            distances[source] = Some(0);
+            queue.push_back(source);
+            while let Some(node) = queue.pop_front() {
+                let distance = distances[node].unwrap();
+                for &neighbor in &lists[node] {
+                    if distances[neighbor].is_none() {
+                        distances[neighbor] = Some(distance + 1);
+                        total += distance + 1;
+                        pairs += 1;
+                        queue.push_back(neighbor);
+                    }
+                }
+            }
+        }
+
+        if pairs == 0 {
+            return 0.0;
+        }
+        total as f64 / pairs as f64
+    }
+
+    /// Every member's neighbours with the cost of reaching them: the inverse
+    /// of the relationship's weight, so a full-strength tie costs one hop and
+    /// weaker ties are longer. A hostile (negative) tie costs as much as a
+    /// friendly one of the same magnitude, which also keeps every cost
+    /// positive for the search.
+    fn weighted_lists(&self) -> Vec<Vec<(usize, f64)>> where T: Into<f64> {
+        self.to_adjacency_list().into_iter()
+            .map(|list| {
+                list.into_iter()
+                    .map(|(neighbor, weight)| (neighbor, 1.0 / weight.into().abs()))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The cost of the cheapest path from `from` to `to`, where each
+    /// relationship costs the inverse of its weight. `None` when `to` cannot
+    /// be reached.
+    fn weighted_shortest_path(&self, from: usize, to: usize) -> Option<f64>
+            where T: Into<f64> {
+        cheapest_paths(&self.weighted_lists(), from)[to]
+    }
+
+    /// The mean cost of the cheapest path between every pair of members that
+    /// can reach each other, as `average_path_length` but with each
+    /// relationship costing the inverse of its weight. Zero when no pair is
+    /// connected.
+    fn average_weighted_path_length(&self) -> f64 where T: Into<f64> {
+        let lists = self.weighted_lists();
+        let mut total = 0.0;
+        let mut pairs = 0;
+        for source in 0..self.size() {
+            for (target, cost) in cheapest_paths(&lists, source).into_iter().enumerate() {
+                if let (true, Some(cost)) = (target != source, cost) {
+                    total += cost;
+                    pairs += 1;
+                }
+            }
+        }
+
+        if pairs == 0 {
+            return 0.0;
+        }
+        total / pairs as f64
+    }
+
+    /// Newman's modularity of the weighted network split into `communities`,
+    /// given as each member's community: the fraction of weight that falls
+    /// within communities, less the fraction expected if ties were placed at
+    /// random between members of the same strength. Each direction of a
+    /// relationship counts as half a tie. Zero for a network without weight.
+    fn modularity(&self, communities: &[usize]) -> f64 where T: Into<f64> {
+        let groups = communities.iter().max().map_or(0, |&last| last + 1);
+        let mut within = vec![0.0; groups];
+        let mut strength = vec![0.0; groups];
+        let mut total = 0.0;
+        for (node, list) in self.to_adjacency_list().into_iter().enumerate() {
+            for (neighbor, weight) in list {
+                let weight = weight.into();
+                if communities[node] == communities[neighbor] {
+                    within[communities[node]] += weight;
+                }
+                strength[communities[node]] += weight;
+                total += weight;
+            }
+        }
+
+        if total == 0.0 {
+            return 0.0;
+        }
+        (0..groups)
+            .map(|group| within[group] / total - (strength[group] / total).powi(2))
+            .sum()
+    }
+
+    /// A regular ring lattice: every member is joined to its k/2 nearest
+    /// members on either side. This is Watts-Strogatz with a beta of 0. A `k`
+    /// below 2 joins no one, leaving an empty network of isolated members.
+    fn ring_lattice(n: usize, k: usize, marker: T) -> Self where Self: Sized {
+        let mut matrix = Self::new(n);
+
+        // Each member is joined to the k/2 members that follow it around the
+        // ring; because `put` is symmetric this also joins it to the k/2
+        // members that precede it, for a degree of k.
+        let half_k = k / 2;
+        for row in 0..n {
+            for offset in 1..(half_k + 1) {
+                let col = (row + offset) % n;
+                matrix.put(row, col, marker);
+            }
+        }
+
+        matrix
+    }
+
+    fn wattz_strogatz<R: Rng>(n: usize, k: usize, beta: f64, marker: T,
+                              rng: &mut R) -> Self where Self: Sized {
+        let mut matrix = Self::ring_lattice(n, k, marker);
+
+        // Rewire each lattice edge with probability beta, moving its far end
+        // to a member picked uniformly from everyone the near end is not yet
+        // connected to. This never creates self-loops or duplicate edges, so
+        // the number of edges stays that of the lattice. Be sure to
+        // symmetically rewire. An empty lattice has nothing to rewire and
+        // draws nothing.
+        let lattice: Vec<(usize, usize)> = (0..n)
+            .flat_map(|row| (0..row).map(move |col| (row, col)))
+            .filter(|&(row, col)| matrix.get(row, col) == marker)
+            .collect();
+        for (row, col) in lattice {
+            if rng.next_f64() >= beta {
+                continue;
+            }
+            let candidates: Vec<usize> = (0..n)
+                .filter(|&other| other != row && matrix.get(row, other) == T::default())
+                .collect();
+            if candidates.is_empty() {
+                continue;
+            }
+
+            let new_col = candidates[rng.gen_range(0, candidates.len())];
+            matrix.put(row, col, T::default());
+            matrix.put(col, row, T::default());
+            matrix.put(row, new_col, marker);
+            matrix.put(new_col, row, marker);
+        }
+
+        matrix
+    }
+
+    fn barabasi_albert<R: Rng>(n: usize, m: usize, marker: T,
+                               rng: &mut R) -> Self where Self: Sized {
+        let mut matrix = Self::new(n);
+
+        // Start from a fully connected seed of m members.
+        let seed_size = if m < n { m } else { n };
+        let mut endpoints: Vec<usize> = Vec::new();
+        for row in 0..seed_size {
+            for col in 0..row {
+                matrix.put(row, col, marker);
+                endpoints.push(row);
+                endpoints.push(col);
+            }
+        }
+
+        // Attach every new member with m edges. Each member appears in
+        // `endpoints` once per edge it has, so sampling from it picks targets
+        // proportionally to their degree.
+        for row in seed_size..n {
+            let mut targets: Vec<usize> = Vec::new();
+            while targets.len() < m {
+                let target = if endpoints.is_empty() {
+                    rng.gen_range(0, row)
+                } else {
+                    endpoints[rng.gen_range(0, endpoints.len())]
+                };
+                if !targets.contains(&target) {
+                    targets.push(target);
+                }
+            }
+
+            for target in targets {
+                matrix.put(row, target, marker);
+                endpoints.push(row);
+                endpoints.push(target);
+            }
+        }
+
+        matrix
+    }
+
+    /// Connect every pair of members, the mean-field limit.
+    fn complete(n: usize, marker: T) -> Self where Self: Sized {
+        let mut matrix = Self::new(n);
+        for row in 0..n {
+            for col in 0..row {
+                matrix.put(row, col, marker);
+            }
+        }
+        matrix
+    }
+
+    /// Connect every pair of members placed within `radius` of each other.
+    fn geometric(positions: &[Position], radius: f64, marker: T) -> Self where Self: Sized {
+        let mut matrix = Self::new(positions.len());
+        for row in 0..positions.len() {
+            for col in 0..row {
+                let (dx, dy) = (positions[row].0 - positions[col].0,
+                                positions[row].1 - positions[col].1);
+                if (dx * dx + dy * dy).sqrt() <= radius {
+                    matrix.put(row, col, marker);
+                }
+            }
+        }
+        matrix
+    }
+
+    /// Load a network of `n` members from a CSV of `source,target[,weight]`
+    /// rows. Pairs without a weight are connected with `marker`. Blank lines
+    /// and a header row are skipped. Directed storage only connects each
+    /// source to its target.
+    fn from_edge_list(path: &str, n: usize, marker: T) -> io::Result<Self>
+            where Self: Sized, T: FromStr {
+        let invalid = |line: usize, message: String| {
+            io::Error::new(io::ErrorKind::InvalidData,
+                           format!("{}:{}: {}", path, line, message))
+        };
+
+        let mut matrix = Self::new(n);
+        let reader = BufReader::new(File::open(path)?);
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            let number = index + 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let fields: Vec<&str> = line.split(',').map(|field| field.trim()).collect();
+            if fields.len() < 2 || fields.len() > 3 {
+                return Err(invalid(number, format!("expected source,target[,weight], found {:?}",
+                                                   line)));
+            }
+
+            let (source, target) = match (fields[0].parse::<usize>(), fields[1].parse::<usize>()) {
+                (Ok(source), Ok(target)) => (source, target),
+                _ if number == 1 => continue,
+                _ => return Err(invalid(number, format!("members must be indices, found {:?}",
+                                                        line)))
+            };
+            for &member in &[source, target] {
+                if member >= n {
+                    return Err(invalid(number, format!("member {} is outside [0, {})",
+                                                       member, n)));
+                }
+            }
+            if source == target {
+                return Err(invalid(number, format!("member {} cannot be connected to itself",
+                                                   source)));
+            }
+
+            let weight = match fields.get(2) {
+                Some(field) => field.parse::<T>()
+                    .map_err(|_| invalid(number, format!("invalid weight {:?}", field)))?,
+                None => marker
+            };
+            matrix.put(source, target, weight);
+        }
+
+        Ok(matrix)
+    }
+
+    /// A random geometric graph: `n` members scattered over the unit square,
+    /// connected when they lie within `radius` of each other.
+    fn random_geometric<R: Rng>(n: usize, radius: f64, marker: T,
+                                rng: &mut R) -> Self where Self: Sized {
+        Self::geometric(&unit_square_positions(n, rng), radius, marker)
+    }
+
+    fn erdos_renyi<R: Rng>(n: usize, p: f64, marker: T,
+                           rng: &mut R) -> Self where Self: Sized {
+        let mut matrix = Self::new(n);
+
+        // Connect every unordered pair independently with probability p.
+        for row in 0..n {
+            for col in 0..row {
+                if rng.next_f64() < p {
+                    matrix.put(row, col, marker);
+                }
+            }
+        }
+
+        matrix
+    }
+
+    /// Split the members into consecutive blocks of the given sizes and
+    /// connect pairs within a block with probability `p_in` and pairs across
+    /// blocks with probability `p_out`.
+    fn stochastic_block_model<R: Rng>(sizes: Vec<usize>, p_in: f64, p_out: f64, marker: T,
+                                      rng: &mut R) -> Self where Self: Sized {
+        let blocks = block_assignment(&sizes);
+        let mut matrix = Self::new(blocks.len());
+
+        for row in 0..blocks.len() {
+            for col in 0..row {
+                let p = if blocks[row] == blocks[col] { p_in } else { p_out };
+                if rng.next_f64() < p {
+                    matrix.put(row, col, marker);
+                }
+            }
+        }
+
+        matrix
+    }
+}
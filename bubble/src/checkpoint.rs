@@ -0,0 +1,201 @@
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::str::FromStr;
+
+use adjacency::Position;
+use model::Opinion;
+use network::Edge;
+
+/// Everything a run needs to carry on from the end of a tick exactly as if it
+/// had never stopped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checkpoint {
+    /// The last tick simulated.
+    pub tick: usize,
+    /// How many words the run's generator had produced.
+    pub draws: u64,
+    /// How many ticks in a row opinions had stayed within the convergence
+    /// epsilon.
+    pub stable_ticks: usize,
+    /// The mean opinion change of each of the last `stable_window` ticks,
+    /// oldest first.
+    pub changes: Vec<f64>,
+    pub directed: bool,
+    pub opinions: Vec<Opinion>,
+    pub stubbornness: Vec<f64>,
+    pub openness: Vec<f64>,
+    /// Where every member sits in a spatial network, which nothing after the
+    /// first tick could redraw.
+    pub positions: Option<Vec<Position>>,
+    pub edges: Vec<Edge>
+}
+
+impl Checkpoint {
+    /// Write the checkpoint as plain text: a few `key value` lines, one line
+    /// per recent opinion change, one line of stubbornness, openness and
+    /// opinion per member, one line of x and y per member of a spatial
+    /// network, then one line per relationship. Weights, opinions and
+    /// positions are written in full so that they read back unchanged.
+    pub fn write<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "tick {}", self.tick)?;
+        writeln!(out, "draws {}", self.draws)?;
+        writeln!(out, "stable_ticks {}", self.stable_ticks)?;
+        writeln!(out, "changes {}", self.changes.len())?;
+        for change in &self.changes {
+            writeln!(out, "{}", change)?;
+        }
+        writeln!(out, "directed {}", self.directed)?;
+        writeln!(out, "members {}", self.opinions.len())?;
+        for (member, opinion) in self.opinions.iter().enumerate() {
+            let values: Vec<String> = opinion.iter().map(|v| v.to_string()).collect();
+            writeln!(out, "{} {} {}", self.stubbornness[member], self.openness[member],
+                     values.join(" "))?;
+        }
+        let positions: &[Position] = self.positions.as_ref().map_or(&[], |p| p.as_slice());
+        writeln!(out, "positions {}", positions.len())?;
+        for &(x, y) in positions {
+            writeln!(out, "{} {}", x, y)?;
+        }
+        writeln!(out, "edges {}", self.edges.len())?;
+        for edge in &self.edges {
+            writeln!(out, "{} {} {}", edge.sender, edge.recipient, edge.weight)?;
+        }
+        Ok(())
+    }
+
+    /// Replace the file at `path` with this checkpoint.
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write(&mut out)?;
+        out.flush()
+    }
+
+    /// Read a checkpoint written by `save`.
+    pub fn from_file(path: &str) -> io::Result<Checkpoint> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut lines = Lines {
+            path,
+            lines: reader.lines().collect::<io::Result<Vec<String>>>()?,
+            read: 0
+        };
+
+        let tick = lines.value("tick")?;
+        let draws = lines.value("draws")?;
+        let stable_ticks = lines.value("stable_ticks")?;
+        let count = lines.value("changes")?;
+        let mut changes = Vec::with_capacity(count);
+        for _ in 0..count {
+            let line = lines.next()?;
+            match line.parse() {
+                Ok(change) => changes.push(change),
+                Err(_) => return Err(lines.error(format!("expected an opinion change, \
+                                                          found {:?}", line)))
+            }
+        }
+        let directed = lines.value("directed")?;
+        let members = lines.value("members")?;
+        let mut checkpoint = Checkpoint {
+            tick,
+            draws,
+            stable_ticks,
+            changes,
+            directed,
+            opinions: Vec::with_capacity(members),
+            stubbornness: Vec::with_capacity(members),
+            openness: Vec::with_capacity(members),
+            positions: None,
+            edges: Vec::new()
+        };
+        for _ in 0..members {
+            let line = lines.next()?;
+            let values: Option<Vec<f64>> = line.split(' ').map(|v| v.parse().ok()).collect();
+            match values {
+                Some(ref values) if values.len() > 2 => {
+                    checkpoint.stubbornness.push(values[0]);
+                    checkpoint.openness.push(values[1]);
+                    checkpoint.opinions.push(values[2..].to_vec());
+                },
+                _ => return Err(lines.error(format!("expected stubbornness, openness and \
+                                                     opinion, found {:?}", line)))
+            }
+        }
+
+        // A network without positions writes none at all.
+        let count: usize = lines.value("positions")?;
+        if count != 0 && count != members {
+            return Err(lines.error(format!("expected 0 or {} positions, found {}", members,
+                                           count)));
+        }
+        let mut positions = Vec::with_capacity(count);
+        for _ in 0..count {
+            let line = lines.next()?;
+            let values: Option<Vec<f64>> = line.split(' ').map(|v| v.parse().ok()).collect();
+            match values {
+                Some(ref values) if values.len() == 2 => positions.push((values[0], values[1])),
+                _ => return Err(lines.error(format!("expected x and y, found {:?}", line)))
+            }
+        }
+        if count != 0 {
+            checkpoint.positions = Some(positions);
+        }
+
+        let edges: usize = lines.value("edges")?;
+        for _ in 0..edges {
+            let line = lines.next()?;
+            let fields: Vec<&str> = line.split(' ').collect();
+            let edge = if fields.len() == 3 {
+                match (fields[0].parse::<usize>(), fields[1].parse::<usize>(),
+                       fields[2].parse::<f64>()) {
+                    (Ok(sender), Ok(recipient), Ok(weight))
+                            if sender < members && recipient < members => {
+                        Some(Edge { sender, recipient, weight })
+                    },
+                    _ => None
+                }
+            } else {
+                None
+            };
+            match edge {
+                Some(edge) => checkpoint.edges.push(edge),
+                None => return Err(lines.error(format!("expected sender, recipient and \
+                                                        weight, found {:?}", line)))
+            }
+        }
+
+        Ok(checkpoint)
+    }
+}
+
+// The lines of a checkpoint file, read in order, with errors that point at
+// the line being read.
+struct Lines<'a> {
+    path: &'a str,
+    lines: Vec<String>,
+    read: usize
+}
+
+impl<'a> Lines<'a> {
+    fn error(&self, message: String) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData,
+                       format!("{}:{}: {}", self.path, self.read, message))
+    }
+
+    fn next(&mut self) -> io::Result<String> {
+        if self.read == self.lines.len() {
+            return Err(self.error("unexpected end of file".to_string()));
+        }
+        self.read += 1;
+        Ok(self.lines[self.read - 1].clone())
+    }
+
+    // Read a `key value` line.
+    fn value<T: FromStr>(&mut self, key: &str) -> io::Result<T> {
+        let line = self.next()?;
+        let mut fields = line.splitn(2, ' ');
+        match (fields.next(), fields.next().map(|value| value.parse::<T>())) {
+            (Some(found), Some(Ok(value))) if found == key => Ok(value),
+            _ => Err(self.error(format!("expected {} followed by a value, found {:?}", key, line)))
+        }
+    }
+}
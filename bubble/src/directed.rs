@@ -0,0 +1,119 @@
+use std;
+
+use adjacency::Adjacency;
+use matrix::Matrix;
+
+/// A dense adjacency structure whose relationships run one way. `put` only
+/// sets the strength from `row` to `col`, so the two sides of a relationship
+/// can drift apart, or one side can disappear entirely.
+#[derive(Debug, Clone)]
+pub struct DirectedMatrix<T> {
+    size: usize,
+    data: Vec<T>
+}
+
+impl<T> DirectedMatrix<T> where T: Default + Copy + PartialEq {
+    pub fn new(size: usize) -> Self {
+        DirectedMatrix {
+            size,
+            data: vec![T::default(); size * size],
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    fn index_for(&self, row: usize, col: usize) -> usize {
+        assert!(row < self.size && col < self.size,
+                "({}, {}) is outside a {}x{} matrix", row, col, self.size, self.size);
+        row * self.size + col
+    }
+
+    /// The strength of the relationship from `row` towards `col`.
+    pub fn get(&self, row: usize, col: usize) -> T {
+        let index = self.index_for(row, col);
+        self.data[index]
+    }
+
+    /// Set the strength from `row` towards `col`, leaving the reverse
+    /// direction alone.
+    pub fn put(&mut self, row: usize, col: usize, value: T) {
+        let index = self.index_for(row, col);
+        self.data[index] = value;
+    }
+
+    /// Set the strength from `row` towards `col` and return what it was.
+    pub fn replace(&mut self, row: usize, col: usize, value: T) -> T {
+        let index = self.index_for(row, col);
+        std::mem::replace(&mut self.data[index], value)
+    }
+}
+
+impl<T> Adjacency<T> for DirectedMatrix<T> where T: Default + Copy + PartialEq {
+    fn new(size: usize) -> Self {
+        DirectedMatrix::new(size)
+    }
+
+    fn size(&self) -> usize {
+        DirectedMatrix::size(self)
+    }
+
+    fn get(&self, row: usize, col: usize) -> T {
+        DirectedMatrix::get(self, row, col)
+    }
+
+    fn put(&mut self, row: usize, col: usize, value: T) {
+        DirectedMatrix::put(self, row, col, value)
+    }
+
+    fn replace(&mut self, row: usize, col: usize, value: T) -> T {
+        DirectedMatrix::replace(self, row, col, value)
+    }
+
+    /// The members `node` has a relationship towards.
+    fn neighbors(&self, node: usize) -> Vec<(usize, T)> {
+        (0..self.size)
+            .map(|col| (col, self.get(node, col)))
+            .filter(|&(_, value)| value != T::default())
+            .collect()
+    }
+
+    /// The number of relationships, counting each direction separately.
+    fn edge_count(&self) -> usize {
+        (0..self.size)
+            .map(|node| self.neighbors(node).iter().filter(|&&(other, _)| other != node).count())
+            .sum()
+    }
+
+    /// The fraction of possible directed relationships that exist.
+    fn density(&self) -> f64 {
+        let n = self.size as f64;
+        if n < 2.0 {
+            return 0.0;
+        }
+        self.edge_count() as f64 / (n * (n - 1.0))
+    }
+
+    fn incoming(&self, node: usize) -> Vec<(usize, T)> {
+        (0..self.size)
+            .map(|row| (row, self.get(row, node)))
+            .filter(|&(_, value)| value != T::default())
+            .collect()
+    }
+}
+
+/// Start from a symmetric network, with both directions of every relationship
+/// holding the same strength.
+impl<T> From<Matrix<T>> for DirectedMatrix<T>
+    where T: Default + Copy + std::fmt::Display + std::cmp::PartialEq {
+    fn from(matrix: Matrix<T>) -> Self {
+        let mut directed = DirectedMatrix::new(matrix.size());
+        for row in 0..matrix.size() {
+            for col in 0..matrix.size() {
+                directed.put(row, col, matrix.get(row, col));
+            }
+        }
+        directed
+    }
+}
@@ -0,0 +1,407 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::io::Write;
+
+use rustc_serialize::json::{Json, ToJson};
+
+use matrix::write_row;
+use model::Opinion;
+use network::{Edge, Network};
+use simulation::{EdgeRecord, Media, SimulationConfig, SimulationResult};
+use stats::{mean_opinion, polarization};
+
+// Attribute names for each opinion dimension. Single-issue runs keep the plain
+// `opinion` name.
+fn opinion_keys(dimensions: usize) -> Vec<String> {
+    if dimensions == 1 {
+        return vec!["opinion".to_string()];
+    }
+    (0..dimensions).map(|dimension| format!("opinion_{}", dimension)).collect()
+}
+
+/// Serialize the network as a GraphML document. Every member carries its
+/// opinion and every connected pair is written once with its weight, or once
+/// per direction for a directed network.
+pub fn write_graphml<W: Write>(out: &mut W, network: &Network,
+                               opinions: &[Opinion]) -> io::Result<()> {
+    let dimensions = opinions.first().map(|opinion| opinion.len()).unwrap_or(1);
+    let keys = opinion_keys(dimensions);
+
+    writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(out, "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">")?;
+    for key in &keys {
+        writeln!(out, "  <key id=\"{0}\" for=\"node\" attr.name=\"{0}\" attr.type=\"double\"/>",
+                 key)?;
+    }
+    writeln!(out, "  <key id=\"weight\" for=\"edge\" attr.name=\"weight\" attr.type=\"double\"/>")?;
+    writeln!(out, "  <graph id=\"G\" edgedefault=\"{}\">", edge_type(network))?;
+
+    for (node, opinion) in opinions.iter().enumerate() {
+        writeln!(out, "    <node id=\"n{}\">", node)?;
+        for (key, value) in keys.iter().zip(opinion) {
+            writeln!(out, "      <data key=\"{}\">{}</data>", key, value)?;
+        }
+        writeln!(out, "    </node>")?;
+    }
+
+    for edge in network.edges() {
+        writeln!(out, "    <edge source=\"n{}\" target=\"n{}\">", edge.sender, edge.recipient)?;
+        writeln!(out, "      <data key=\"weight\">{}</data>", edge.weight)?;
+        writeln!(out, "    </edge>")?;
+    }
+
+    writeln!(out, "  </graph>")?;
+    writeln!(out, "</graphml>")
+}
+
+/// How `write_matrix` lays out the adjacency matrix.
+#[derive(Debug, Clone, Copy, PartialEq, RustcDecodable)]
+pub enum MatrixFormat {
+    /// Every weight, a row per line separated by spaces, as `Matrix::print`
+    /// writes it.
+    Dense,
+    /// A `row,col,weight` line per non-zero weight, which
+    /// `Adjacency::from_edge_list` reads back.
+    Triplets
+}
+
+/// Serialize the weighted adjacency matrix of the network. Both formats list
+/// an undirected relationship from each side.
+pub fn write_matrix<W: Write>(out: &mut W, network: &Network,
+                              format: MatrixFormat) -> io::Result<()> {
+    for row in 0..network.size() {
+        match format {
+            MatrixFormat::Dense => {
+                write_row(out, (0..network.size()).map(|col| network.weight(row, col)))?;
+            },
+            MatrixFormat::Triplets => {
+                for (col, weight) in network.neighbors(row) {
+                    writeln!(out, "{},{},{}", row, col, weight)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn edge_type(network: &Network) -> &'static str {
+    if network.is_directed() { "directed" } else { "undirected" }
+}
+
+// Collapse a series of (tick, value) observations into the ticks at which the
+// value actually changed.
+fn spells<T: PartialEq + Copy>(values: &[(usize, T)]) -> Vec<(usize, T)> {
+    let mut spells: Vec<(usize, T)> = Vec::new();
+    for &(tick, value) in values {
+        if spells.last().is_none_or(|&(_, last)| last != value) {
+            spells.push((tick, value));
+        }
+    }
+    spells
+}
+
+// Write one `attvalue` per spell. Each spell lasts until the next one starts;
+// the last is left open until the end of the run.
+fn write_spells<W: Write>(out: &mut W, key: &str, spells: &[(usize, f64)],
+                          indent: &str) -> io::Result<()> {
+    for (index, &(start, value)) in spells.iter().enumerate() {
+        match spells.get(index + 1) {
+            Some(&(end, _)) => writeln!(out,
+                "{}<attvalue for=\"{}\" value=\"{}\" start=\"{}\" endopen=\"{}\"/>",
+                indent, key, value, start, end)?,
+            None => writeln!(out, "{}<attvalue for=\"{}\" value=\"{}\" start=\"{}\"/>",
+                             indent, key, value, start)?
+        }
+    }
+    Ok(())
+}
+
+/// Serialize a run as a dynamic GEXF document for Gephi's timeline. Member
+/// opinions and relationship weights are written as spells keyed by tick,
+/// starting from the state before the first tick.
+pub fn write_gexf<W: Write>(out: &mut W, result: &SimulationResult) -> io::Result<()> {
+    let dimensions = result.initial_opinions.first()
+        .map(|opinion| opinion.len()).unwrap_or(1);
+    let keys = opinion_keys(dimensions);
+    let ticks: Vec<&Vec<Opinion>> = Some(&result.initial_opinions).into_iter()
+        .chain(result.opinion_history.iter())
+        .collect();
+
+    writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(out, "<gexf xmlns=\"http://www.gexf.net/1.2draft\" version=\"1.2\">")?;
+    writeln!(out, "  <graph mode=\"dynamic\" defaultedgetype=\"{}\" timeformat=\"double\">",
+             edge_type(&result.network))?;
+    writeln!(out, "    <attributes class=\"node\" mode=\"dynamic\">")?;
+    for key in &keys {
+        writeln!(out, "      <attribute id=\"{0}\" title=\"{0}\" type=\"double\"/>", key)?;
+    }
+    writeln!(out, "    </attributes>")?;
+
+    writeln!(out, "    <nodes>")?;
+    for node in 0..result.initial_opinions.len() {
+        writeln!(out, "      <node id=\"{0}\" label=\"{0}\">", node)?;
+        writeln!(out, "        <attvalues>")?;
+        for (dimension, key) in keys.iter().enumerate() {
+            let values: Vec<(usize, f64)> = ticks.iter().enumerate()
+                .map(|(offset, opinions)| (result.first_tick + offset, opinions[node][dimension]))
+                .collect();
+            write_spells(out, key, &spells(&values), "          ")?;
+        }
+        writeln!(out, "        </attvalues>")?;
+        writeln!(out, "      </node>")?;
+    }
+    writeln!(out, "    </nodes>")?;
+
+    // The history lists a relationship from whichever side sent the message,
+    // so key every observation by the unordered pair unless the directions are
+    // separate relationships.
+    let directed = result.network.is_directed();
+    let mut weights: BTreeMap<(usize, usize), Vec<(usize, f64)>> = BTreeMap::new();
+    for record in &result.edge_history {
+        let pair = if directed || record.sender > record.recipient {
+            (record.sender, record.recipient)
+        } else {
+            (record.recipient, record.sender)
+        };
+        weights.entry(pair).or_default().push((record.tick, record.weight));
+    }
+
+    writeln!(out, "    <edges>")?;
+    for (id, (&(source, target), values)) in weights.iter().enumerate() {
+        writeln!(out, "      <edge id=\"{}\" source=\"{}\" target=\"{}\">", id, source, target)?;
+        writeln!(out, "        <attvalues>")?;
+        write_spells(out, "weight", &spells(values), "          ")?;
+        writeln!(out, "        </attvalues>")?;
+        writeln!(out, "      </edge>")?;
+    }
+    writeln!(out, "    </edges>")?;
+
+    writeln!(out, "  </graph>")?;
+    writeln!(out, "</gexf>")
+}
+
+impl ToJson for Edge {
+    fn to_json(&self) -> Json {
+        let mut object = BTreeMap::new();
+        object.insert("sender".to_string(), self.sender.to_json());
+        object.insert("recipient".to_string(), self.recipient.to_json());
+        object.insert("weight".to_string(), self.weight.to_json());
+        Json::Object(object)
+    }
+}
+
+impl ToJson for Media {
+    fn to_json(&self) -> Json {
+        let mut object = BTreeMap::new();
+        object.insert("opinion".to_string(), self.opinion.to_json());
+        object.insert("reach".to_string(), self.reach.to_json());
+        object.insert("strength".to_string(), self.strength.to_json());
+        Json::Object(object)
+    }
+}
+
+impl ToJson for EdgeRecord {
+    fn to_json(&self) -> Json {
+        Edge { sender: self.sender, recipient: self.recipient, weight: self.weight }.to_json()
+    }
+}
+
+impl ToJson for SimulationConfig {
+    fn to_json(&self) -> Json {
+        let mut object = BTreeMap::new();
+        object.insert("population".to_string(), self.population.to_json());
+        object.insert("degree".to_string(), self.degree.to_json());
+        object.insert("rewire".to_string(), self.rewire.to_json());
+        object.insert("consensus".to_string(), self.consensus.to_json());
+        object.insert("opposition".to_string(), self.opposition.to_json());
+        object.insert("seed".to_string(), self.seed.to_json());
+        object.insert("topology".to_string(), format!("{:?}", self.topology).to_json());
+        object.insert("radius".to_string(), self.radius.to_json());
+        object.insert("initial_weight".to_string(), self.initial_weight.to_json());
+        object.insert("randomize_weights".to_string(), self.randomize_weights.to_json());
+        object.insert("homophilous_init".to_string(), self.homophilous_init.to_json());
+        object.insert("block_sizes".to_string(), self.block_sizes.to_json());
+        object.insert("p_in".to_string(), self.p_in.to_json());
+        object.insert("p_out".to_string(), self.p_out.to_json());
+        object.insert("sparse".to_string(), self.sparse.to_json());
+        object.insert("model".to_string(), format!("{:?}", self.model).to_json());
+        object.insert("confidence_bound".to_string(), self.confidence_bound.0.to_json());
+        object.insert("convergence_rate".to_string(), self.convergence_rate.0.to_json());
+        object.insert("neutral_drift".to_string(), self.neutral_drift.to_json());
+        object.insert("assimilation_rate".to_string(), self.assimilation_rate.to_json());
+        object.insert("inertia".to_string(), self.inertia.to_json());
+        object.insert("directed".to_string(), self.directed.to_json());
+        object.insert("grudges".to_string(), self.grudges.to_json());
+        object.insert("signed".to_string(), self.signed.to_json());
+        object.insert("mutual".to_string(), self.mutual.to_json());
+        object.insert("backfire".to_string(), self.backfire.to_json());
+        object.insert("dimensions".to_string(), self.dimensions.to_json());
+        object.insert("opinion_min".to_string(), self.opinion_min.to_json());
+        object.insert("opinion_max".to_string(), self.opinion_max.to_json());
+        object.insert("boundary".to_string(), format!("{:?}", self.boundary).to_json());
+        object.insert("zealots".to_string(), self.zealots.to_json());
+        object.insert("media".to_string(), self.media.to_json());
+        object.insert("init_distribution".to_string(),
+                      format!("{:?}", self.init_distribution).to_json());
+        object.insert("openness_distribution".to_string(),
+                      format!("{:?}", self.openness_distribution).to_json());
+        object.insert("init_mean".to_string(), self.init_mean.to_json());
+        object.insert("init_stddev".to_string(), self.init_stddev.to_json());
+        object.insert("init_separation".to_string(), self.init_separation.to_json());
+        object.insert("message_stddev".to_string(), self.message_stddev.to_json());
+        object.insert("noise_schedule".to_string(),
+                      format!("{:?}", self.noise_schedule).to_json());
+        object.insert("final_message_stddev".to_string(), self.final_message_stddev.to_json());
+        object.insert("ticks".to_string(), self.ticks.to_json());
+        object.insert("messages_per_tick".to_string(), self.messages_per_tick.to_json());
+        object.insert("reach_weighting".to_string(), self.reach_weighting.to_json());
+        object.insert("rewire_on_conflict".to_string(), self.rewire_on_conflict.to_json());
+        object.insert("min_active_weight".to_string(), self.min_active_weight.to_json());
+        object.insert("full_network_dump".to_string(), self.full_network_dump.to_json());
+        object.insert("threads".to_string(), self.threads.to_json());
+        object.insert("convergence_epsilon".to_string(), self.convergence_epsilon.to_json());
+        object.insert("stable_window".to_string(), self.stable_window.to_json());
+        object.insert("burn_in".to_string(), self.burn_in.to_json());
+        object.insert("record_history".to_string(), self.record_history.to_json());
+        object.insert("checkpoint_interval".to_string(), self.checkpoint_interval.to_json());
+        object.insert("checkpoint_path".to_string(), self.checkpoint_path.to_json());
+        // A given network, given opinions and a checkpoint to resume from are
+        // described by their size rather than written out in full.
+        let network = self.network.as_ref().map(|network| {
+            let mut fields = BTreeMap::new();
+            fields.insert("members".to_string(), network.size().to_json());
+            fields.insert("edges".to_string(), network.edges().len().to_json());
+            Json::Object(fields)
+        });
+        object.insert("network".to_string(), network.unwrap_or(Json::Null));
+        let opinions = self.opinions.as_ref().map(|opinions| {
+            let mut fields = BTreeMap::new();
+            fields.insert("members".to_string(), opinions.len().to_json());
+            Json::Object(fields)
+        });
+        object.insert("opinions".to_string(), opinions.unwrap_or(Json::Null));
+        let resume = self.resume.as_ref().map(|checkpoint| {
+            let mut fields = BTreeMap::new();
+            fields.insert("tick".to_string(), checkpoint.tick.to_json());
+            fields.insert("draws".to_string(), checkpoint.draws.to_json());
+            Json::Object(fields)
+        });
+        object.insert("resume".to_string(), resume.unwrap_or(Json::Null));
+        Json::Object(object)
+    }
+}
+
+/// Everything but the parameters of the run: the network before the first
+/// tick, every member's opinion at every tick from the one the run started
+/// at, and the network as it ended.
+impl ToJson for SimulationResult {
+    fn to_json(&self) -> Json {
+        let initial_network: Vec<Json> = self.edge_history.iter()
+            .take_while(|record| record.tick == self.first_tick)
+            .map(|record| record.to_json())
+            .collect();
+        let trajectory: Vec<Json> = Some(&self.initial_opinions).into_iter()
+            .chain(self.opinion_history.iter())
+            .map(|opinions| opinions.to_json())
+            .collect();
+        let final_network: Vec<Json> = self.network.edges().iter()
+            .map(|edge| edge.to_json())
+            .collect();
+
+        let mut object = BTreeMap::new();
+        object.insert("first_tick".to_string(), self.first_tick.to_json());
+        object.insert("converged_at".to_string(), self.converged_at.to_json());
+        object.insert("oscillating".to_string(), self.oscillating.to_json());
+        object.insert("edge_count".to_string(), self.initial_stats.edge_count.to_json());
+        object.insert("density".to_string(), self.initial_stats.density.to_json());
+        object.insert("components".to_string(), self.initial_stats.components.to_json());
+        object.insert("largest_component".to_string(),
+                      self.initial_stats.largest_component.to_json());
+        object.insert("clustering_coefficient".to_string(),
+                      self.initial_stats.clustering_coefficient.to_json());
+        object.insert("average_path_length".to_string(),
+                      self.initial_stats.average_path_length.to_json());
+        if let Some(ref positions) = self.positions {
+            let positions: Vec<Json> = positions.iter()
+                .map(|&(x, y)| vec![x, y].to_json())
+                .collect();
+            object.insert("positions".to_string(), Json::Array(positions));
+        }
+        object.insert("openness".to_string(), self.openness.to_json());
+        object.insert("initial_network".to_string(), Json::Array(initial_network));
+        object.insert("opinions".to_string(), Json::Array(trajectory));
+        object.insert("final_network".to_string(), Json::Array(final_network));
+        Json::Object(object)
+    }
+}
+
+// A parameter's value as plain text: numbers as Rust prints them, nothing for
+// a missing value, and the items of a list or the fields of a group separated
+// by spaces.
+fn plain(value: &Json) -> String {
+    match *value {
+        Json::String(ref value) => value.clone(),
+        Json::F64(value) => value.to_string(),
+        Json::I64(value) => value.to_string(),
+        Json::U64(value) => value.to_string(),
+        Json::Boolean(value) => value.to_string(),
+        Json::Null => String::new(),
+        Json::Array(ref values) => {
+            values.iter().map(plain).collect::<Vec<String>>().join(" ")
+        },
+        Json::Object(ref fields) => {
+            fields.iter()
+                .map(|(name, value)| format!("{}:{}", name, plain(value)))
+                .collect::<Vec<String>>()
+                .join(" ")
+        }
+    }
+}
+
+/// Every parameter of the run by name, in alphabetical order, with the same
+/// names as the parameters of the JSON document. Values are plain text, as
+/// both the metadata file and `write_parameters` write them.
+pub fn parameters(config: &SimulationConfig) -> Vec<(String, String)> {
+    match config.to_json() {
+        Json::Object(parameters) => {
+            parameters.iter().map(|(name, value)| (name.clone(), plain(value))).collect()
+        },
+        _ => unreachable!()
+    }
+}
+
+/// Write every parameter of the run as a `name: value` line, leaving the
+/// value off a parameter that was not set.
+pub fn write_parameters<W: Write>(out: &mut W, config: &SimulationConfig) -> io::Result<()> {
+    for (name, value) in parameters(config) {
+        if value.is_empty() {
+            writeln!(out, "{}:", name)?;
+        } else {
+            writeln!(out, "{}: {}", name, value)?;
+        }
+    }
+    Ok(())
+}
+
+/// Write one tick as a single line of JSON holding its mean opinion and
+/// variance, for consumers that read a run while it is still going.
+pub fn write_tick_json<W: Write>(out: &mut W, tick: usize,
+                                 opinions: &[Opinion]) -> io::Result<()> {
+    let mut object = BTreeMap::new();
+    object.insert("tick".to_string(), tick.to_json());
+    object.insert("mean".to_string(), mean_opinion(opinions).to_json());
+    object.insert("variance".to_string(), polarization(opinions).to_json());
+    writeln!(out, "{}", Json::Object(object))
+}
+
+/// Serialize a whole run as a single JSON document, with the parameters that
+/// produced it under `parameters`.
+pub fn write_json<W: Write>(out: &mut W, config: &SimulationConfig,
+                            result: &SimulationResult) -> io::Result<()> {
+    let mut document = match result.to_json() {
+        Json::Object(object) => object,
+        _ => unreachable!()
+    };
+    document.insert("parameters".to_string(), config.to_json());
+    writeln!(out, "{}", Json::Object(document))
+}
@@ -0,0 +1,31 @@
+extern crate rand;
+extern crate rayon;
+extern crate rustc_serialize;
+
+mod adjacency;
+mod checkpoint;
+mod directed;
+mod export;
+mod matrix;
+mod model;
+mod network;
+mod simulation;
+mod sparse;
+mod stats;
+
+pub use adjacency::{block_assignment, unit_square_positions, Adjacency, Position};
+pub use checkpoint::Checkpoint;
+pub use directed::DirectedMatrix;
+pub use export::{parameters, write_gexf, write_graphml, write_json, write_matrix,
+                 write_parameters, write_tick_json, MatrixFormat};
+pub use matrix::Matrix;
+pub use model::{distance, hegselmann_krause, read_opinions, ConfidenceBound, ConvergenceRate,
+                DeffuantModel, Model, Opinion, OpinionModel, Outcome, ThresholdModel};
+pub use network::{Edge, Network, NetworkStats, Topology};
+pub use simulation::{build_network, simulate, simulate_observed, simulate_with, Boundary,
+                     EdgeEvent, EdgeEventKind, EdgeRecord, InitialDistribution, Media,
+                     NoiseSchedule, OpennessDistribution, SimulationConfig, SimulationResult,
+                     SimulationRng, TickState, TickSummary};
+pub use sparse::SparseMatrix;
+pub use stats::{histogram, mean, mean_opinion, opinion_clusters, opinion_entropy, polarization,
+                variance, OpinionCluster};
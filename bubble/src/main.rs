@@ -1,274 +1,1694 @@
+extern crate bubbles;
 extern crate rand;
 extern crate chrono;
 extern crate rustc_serialize;
 extern crate docopt;
+extern crate flate2;
+extern crate rayon;
+extern crate toml;
 
-use rand::Rng;
-use rand::distributions::{IndependentSample, Range, Normal};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
 use std::fs::File;
-use std::io::Write;
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::process;
 use chrono::prelude::*;
 use docopt::Docopt;
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use rand::{Rng, SeedableRng, StdRng};
+use rayon::prelude::*;
+
+use bubbles::{Adjacency, Boundary, Checkpoint, ConfidenceBound, ConvergenceRate,
+              DirectedMatrix, EdgeEventKind, InitialDistribution, Matrix, MatrixFormat, Media,
+              Model, Network, NoiseSchedule, OpennessDistribution, Opinion, SimulationConfig,
+              SimulationResult, SparseMatrix, TickState, Topology};
 
 const USAGE: &'static str = "
 Social Network Bubble Simulator.
 
 Usage:
-  bubble <population> <degree> <rewire> <consensus> <opposition>
+  bubble <population> <degree> <rewire> <consensus> <opposition> [options]
+  bubble --config=<path> [options]
+  bubble --replay=<path> [options]
+
+Options:
+  --verbose                        Print every parameter of the run, after
+                                   defaults and --config are applied, to stderr
+                                   before it starts.
+  --dry-run                        Build the network, print its statistics and
+                                   degree distribution, and exit without
+                                   simulating or writing any files.
+  --sweep=<path>                   Run every combination of the rewire,
+                                   consensus and opposition values in this TOML
+                                   file, each a list of numbers or a table of
+                                   start, stop and step, in parallel. Writes
+                                   only <prefix>_sweep.csv with one summary row
+                                   per run. Parameters missing from the file
+                                   keep their command line values.
+  --repeats=<n>                    Run the parameters this many times in
+                                   parallel, each with a seed drawn from the
+                                   generator for --seed. Writes only
+                                   <prefix>_repeats.csv with the mean and
+                                   standard deviation of the final polarization,
+                                   cluster count and consensus fraction across
+                                   runs, and <prefix>_replicates.csv with each
+                                   run's seed and outcome.
+  --seeds=<list>                   Run one repeat per seed in this
+                                   comma-separated list instead of drawing the
+                                   seeds from --seed. Implies --repeats.
+  --replay=<path>                  Read a network CSV written by an earlier run,
+                                   gzipped or not, and print the network as it
+                                   stood after every tick with rows in it as
+                                   frame,tick,sender,recipient,weight. Rows
+                                   after tick 0 update the relationships carried
+                                   over from before, so files that only hold the
+                                   relationships that changed replay the same
+                                   way as full dumps. Pass --directed for a
+                                   directed run's file.
+  --config=<path>                  Read parameters from a TOML file. Keys are
+                                   the positional arguments and option names
+                                   without the leading dashes, e.g. population =
+                                   100 or full-network-dump = true. Options
+                                   given on the command line override the file.
+  --ticks=<ticks>                  Number of ticks to simulate. [default: 10000]
+  --messages-per-tick=<n>          Random senders that broadcast during each
+                                   tick. Raise it with the population to keep
+                                   runs of different sizes comparable.
+                                   [default: 1]
+  --reach-weighting                Scale each message's pull on its recipients
+                                   by the sender's summed relationship strength
+                                   relative to the population mean.
+  --rewire-on-conflict             When a relationship falls to 0, the recipient
+                                   forms a new one to a random member within
+                                   <consensus> of its opinion.
+  --min-active-weight=<w>          Relationships weaker than this go dormant:
+                                   messages along them still change their
+                                   strength, so they can recover, but move no
+                                   opinions. Not available with hk.
+                                   [default: 0.0]
+  --threads=<n>                    Work out the outcomes of each broadcast in
+                                   parallel on this many threads. Every outcome
+                                   of a broadcast is then computed from the
+                                   opinions at its start, so results match
+                                   across thread counts.
+  --convergence-epsilon=<epsilon>  Stop early once no opinion moves by more than
+                                   this for --stable-window consecutive ticks.
+  --stable-window=<ticks>          Consecutive quiet ticks required to stop
+                                   early. A run that does not stop is marked
+                                   oscillating in the metadata when the mean
+                                   opinion change over its last window of ticks
+                                   did not decay. [default: 100]
+  --burn-in=<ticks>                Treat the ticks before this one as transient.
+                                   Convergence and oscillation ignore them, and
+                                   so do the summary, polarization and entropy
+                                   outputs. [default: 0]
+  --checkpoint-interval=<ticks>    Save the state of the run to
+                                   <prefix>_checkpoint.txt every this many
+                                   ticks, replacing the previous checkpoint.
+  --resume=<path>                  Carry on from a checkpoint exactly where it
+                                   left off. Pass the same parameters as the run
+                                   that wrote it. Outputs cover only the ticks
+                                   after the checkpoint.
+  --output-dir=<path>              Directory to write the CSV files to. It is
+                                   created if missing. Defaults to the current
+                                   directory.
+  --prefix=<prefix>                Name the CSV files <prefix>_simulation.csv,
+                                   <prefix>_network.csv, <prefix>_metadata.csv
+                                   and <prefix>_final_opinions.csv instead of
+                                   using the start time.
+  --opinion-sample-interval=<n>    Only write opinions at tick 0, every <n>th
+                                   tick and the final tick.
+  --opinion-format=<layout>        Write the opinion CSV with one row per member
+                                   per tick (long) or one row per tick and a
+                                   column for each member (wide).
+                                   [default: long]
+  --format=<format>                Write the run as three CSV files (csv), as a
+                                   single JSON document (json), or as one line
+                                   of JSON per tick on stdout while it runs
+                                   (jsonl), holding the tick, mean opinion and
+                                   variance. [default: csv]
+  --compress                       Gzip the opinion and network output (or the
+                                   JSON document) and add .gz to their names.
+  --progress                       Print the tick, mean opinion and variance to
+                                   stderr every 1% of the run.
+  --no-header                      Do not write a header row to the CSV files.
+  --export-graphml=<path>          Also write the final network, with each
+                                   member's opinion, as GraphML.
+  --export-gexf=<path>             Also write the whole run as a dynamic GEXF
+                                   file, with opinions and weights changing over
+                                   time.
+  --export-matrix=<path>           Also write the weighted adjacency matrix.
+  --matrix-format=<format>         Write the matrix as a dense grid (dense) or
+                                   as row,col,weight lines that --network-input
+                                   reads back (triplets). [default: dense]
+  --matrix-state=<state>           Export the network before the first tick
+                                   (initial) or as it ended (final).
+                                   [default: initial]
+  --path-report=<path>             Also write the average weighted path length,
+                                   with each relationship costing the inverse of
+                                   its weight, before and after the run.
+  --degree-histogram=<path>        Also write how many members have each degree
+                                   in the initial network.
+  --polarization=<path>            Also write the polarization (opinion
+                                   variance) of the population at every tick.
+  --entropy=<path>                 Also write the Shannon entropy of the
+                                   opinions, binned into --entropy-bins buckets
+                                   per dimension, at every tick kept by
+                                   --opinion-sample-interval.
+  --entropy-bins=<n>               How many equal buckets the opinion range is
+                                   split into for the entropy. [default: 10]
+  --opinion-heatmap=<path>         Also write how many members hold an opinion
+                                   in each of --heatmap-bins equal buckets of
+                                   the opinion range, as tick,bin,count rows, at
+                                   every tick kept by --opinion-sample-interval.
+                                   Runs with several dimensions add a dimension
+                                   column.
+  --heatmap-bins=<n>               How many equal buckets the opinion range is
+                                   split into for the heatmap. [default: 20]
+  --summary=<path>                 Also write the mean, variance, minimum and
+                                   maximum opinion and the number of
+                                   relationships at every tick.
+  --neighbor-field=<path>          Also write the weighted mean opinion of each
+                                   member's neighbours at every tick kept by
+                                   --opinion-sample-interval.
+  --node-table=<path>              Also write a node table for Gephi with each
+                                   member's opinion and number of active
+                                   relationships at every tick kept by
+                                   --opinion-sample-interval.
+  --events=<path>                  Also write every relationship that formed
+                                   (weight rose from 0) or was removed (weight
+                                   fell to 0).
+  --cluster-threshold=<gap>        Opinions closer than this end up in the same
+                                   final opinion cluster. [default: 5.0]
+  --seed=<seed>                    Seed for the random number generator. When
+                                   omitted, a seed is drawn from entropy and
+                                   recorded in the metadata file.
+  --network-input=<path>           Simulate on the network in this CSV of
+                                   source,target[,weight] rows instead of
+                                   generating one. Members are numbered from 0
+                                   to <population> - 1 and <degree>, <rewire>
+                                   and --topology are ignored.
+  --topology=<topology>            Network generator: ws (Watts-Strogatz), ring
+                                   (a regular ring lattice, ws with a <rewire>
+                                   of 0), ba (Barabási-Albert), er
+                                   (Erdős-Rényi), each tuned to a mean degree of
+                                   <degree>, complete (everyone connected,
+                                   ignoring <degree>), geometric (members
+                                   scattered over the unit square, connected
+                                   within --radius, ignoring <degree>) or sbm (a
+                                   stochastic block model over --block-sizes,
+                                   ignoring <degree>). [default: ws]
+  --radius=<r>                     How close members of a geometric network must
+                                   be to connect. Their positions are written to
+                                   the metadata. [default: 0.1]
+  --block-sizes=<sizes>            Comma-separated sizes of the blocks of an sbm
+                                   network, adding up to <population>. Each
+                                   member's block is written to the metadata.
+  --p-in=<p>                       Chance that two members of the same sbm block
+                                   are connected. [default: 0.5]
+  --p-out=<p>                      Chance that two members of different sbm
+                                   blocks are connected. [default: 0.05]
+  --initial-weight=<w>             Strength every generated relationship starts
+                                   with, and every relationship formed when
+                                   rewiring on conflict. Rows of a network input
+                                   without a weight also start with it.
+                                   [default: 0.5]
+  --randomize-weights              Draw each generated relationship's starting
+                                   strength uniformly from (0, 1] instead.
+  --homophilous-init               Start each relationship with a strength that
+                                   falls from 1 for identical initial opinions
+                                   to 0 for opinions a whole range apart, so
+                                   that like-minded members start closer.
+  --directed                       Let each direction of a relationship carry
+                                   its own strength. Influence flows from sender
+                                   to recipient only.
+  --grudges                        With --directed, have a message that weakens
+                                   a relationship weaken the recipient's tie to
+                                   the sender rather than the sender's tie to
+                                   the recipient.
+  --signed                         Let relationships range from -1 to 1. A
+                                   negative tie is antagonistic: its pull is
+                                   reversed, so its messages push the recipient
+                                   away. Relationships read with --network-input
+                                   may then be negative. Not available with hk.
+  --sparse                         Store only connected pairs. Use this for
+                                   large, sparse populations.
+  --mutual                         Have recipients reply so that threshold
+                                   senders are persuaded too. Cannot be combined
+                                   with --directed.
+  --full-network-dump              Record every edge on every tick rather than
+                                   only the relationships that changed.
+  --model=<model>                  Opinion update rule: threshold, deffuant or
+                                   hk (Hegselmann-Krause). [default: threshold]
+  --neutral-drift=<factor>         Fraction of the usual pull towards a message
+                                   that threshold members still feel when the
+                                   difference lies between <consensus> and
+                                   <opposition>. [default: 0.0]
+  --assimilation-rate=<rate>       Multiplier on how far threshold members move
+                                   towards or away from a message, independent
+                                   of relationship strength. [default: 1.0]
+  --inertia=<share>                Share of their previous opinion members keep
+                                   after every change, smoothing their
+                                   trajectories. 0 leaves changes as they are.
+                                   [default: 0.0]
+  --no-backfire                    Stop threshold members moving away from
+                                   messages beyond <opposition>. Those messages
+                                   still weaken the relationship.
+  --confidence-bound=<bound>       Largest opinion difference at which deffuant
+                                   and hk members interact. [default: 20.0]
+  --dimensions=<n>                 Number of independent issues each opinion
+                                   covers. Differences are Euclidean distances.
+                                   [default: 1]
+  --opinion-min=<min>              Lowest opinion anyone can hold.
+                                   [default: 0.0]
+  --opinion-max=<max>              Highest opinion anyone can hold. Thresholds,
+                                   bounds and the initial distribution are in
+                                   the same units. [default: 100.0]
+  --thresholds-relative            Read <consensus> and <opposition>, and the
+                                   values a sweep takes for them, as fractions
+                                   of the opinion range, so 0.25 means within a
+                                   quarter of the scale.
+  --boundary=<rule>                What happens to an opinion pushed out of the
+                                   opinion range: clamp pins it to the edge,
+                                   reflect bounces it back inside.
+                                   [default: clamp]
+  --zealots=<fraction>             Fraction of the population that never changes
+                                   its opinion. [default: 0.0]
+  --media-opinion=<opinion>        Broadcast this opinion to part of the
+                                   population every tick, as a mass media source
+                                   would. Not available with hk.
+  --media-reach=<fraction>         Fraction of the population each broadcast
+                                   reaches. [default: 0.1]
+  --media-strength=<strength>      How strong a tie the audience treats the
+                                   broadcast as coming over. [default: 0.5]
+  --convergence-rate=<rate>        Fraction of the difference deffuant members
+                                   close per interaction. [default: 0.3]
+  --opinions-input=<path>          Start from the opinions in this CSV of
+                                   node,opinion rows, with one opinion column
+                                   per dimension, instead of drawing them. Every
+                                   member needs exactly one row within the
+                                   opinion range.
+  --init-distribution=<dist>       How initial opinions are drawn: normal,
+                                   uniform over the opinion range, or bimodal
+                                   (two normals --init-separation apart).
+                                   [default: normal]
+  --openness-distribution=<dist>   How much each member lets messages move it:
+                                   constant (fully open) or uniform over [0, 1].
+                                   [default: constant]
+  --init-mean=<mean>               Centre of the initial opinions.
+                                   [default: 50.0]
+  --init-separation=<gap>          Distance between the two peaks of a bimodal
+                                   start. [default: 50.0]
+  --init-stddev=<sd>               Standard deviation of the initial opinions.
+                                   [default: 10.0]
+  --message-stddev=<sd>            Standard deviation of each message around its
+                                   sender's opinion. Near 0 makes communication
+                                   noise-free. [default: 10.0]
+  --noise-schedule=<schedule>      How the message spread changes over the run:
+                                   constant, or falling from --message-stddev on
+                                   the first tick to --final-message-stddev on
+                                   the last in equal steps (linear) or by a
+                                   fixed factor per tick (exponential).
+                                   [default: constant]
+  --final-message-stddev=<sd>      Message spread on the last tick when the
+                                   noise schedule decays. [default: 1.0]
 ";
 
 
+#[derive(Debug, Clone, Copy, PartialEq, RustcDecodable)]
+enum Format {
+    Csv,
+    Json,
+    Jsonl
+}
+
+/// Which network --export-matrix writes.
+#[derive(Debug, Clone, Copy, PartialEq, RustcDecodable)]
+enum MatrixState {
+    Initial,
+    Final
+}
+
+/// The layout of the opinion CSV.
+#[derive(Debug, Clone, Copy, PartialEq, RustcDecodable)]
+enum OpinionFormat {
+    /// One row per member per tick.
+    Long,
+    /// One row per tick with a column per member.
+    Wide
+}
+
 #[derive(Debug, RustcDecodable)]
 struct Args {
 	arg_population: usize,
 	arg_degree: usize,
 	arg_rewire: f64,
 	arg_consensus: f64,
-	arg_opposition: f64
+	arg_opposition: f64,
+	flag_seed: Option<u64>,
+	flag_topology: Topology,
+	flag_radius: f64,
+	flag_initial_weight: f64,
+	flag_randomize_weights: bool,
+	flag_homophilous_init: bool,
+	flag_block_sizes: Option<String>,
+	flag_p_in: f64,
+	flag_p_out: f64,
+	flag_network_input: Option<String>,
+	flag_opinions_input: Option<String>,
+	flag_sparse: bool,
+	flag_directed: bool,
+	flag_grudges: bool,
+	flag_signed: bool,
+	flag_mutual: bool,
+	flag_full_network_dump: bool,
+	flag_model: Model,
+	flag_confidence_bound: f64,
+	flag_neutral_drift: f64,
+	flag_assimilation_rate: f64,
+	flag_no_backfire: bool,
+	flag_inertia: f64,
+	flag_convergence_rate: f64,
+	flag_dimensions: usize,
+	flag_zealots: f64,
+	flag_media_opinion: Option<f64>,
+	flag_media_reach: f64,
+	flag_media_strength: f64,
+	flag_ticks: usize,
+	flag_messages_per_tick: usize,
+	flag_reach_weighting: bool,
+	flag_rewire_on_conflict: bool,
+	flag_min_active_weight: f64,
+	flag_threads: Option<usize>,
+	flag_convergence_epsilon: Option<f64>,
+	flag_stable_window: usize,
+	flag_burn_in: usize,
+	flag_checkpoint_interval: Option<usize>,
+	flag_resume: Option<String>,
+	flag_format: Format,
+	flag_opinion_format: OpinionFormat,
+	flag_opinion_sample_interval: Option<usize>,
+	flag_compress: bool,
+	flag_no_header: bool,
+	flag_dry_run: bool,
+	flag_verbose: bool,
+	flag_sweep: Option<String>,
+	flag_repeats: Option<usize>,
+	flag_seeds: Option<String>,
+	flag_progress: bool,
+	flag_export_graphml: Option<String>,
+	flag_export_gexf: Option<String>,
+	flag_export_matrix: Option<String>,
+	flag_matrix_format: MatrixFormat,
+	flag_matrix_state: MatrixState,
+	flag_output_dir: Option<String>,
+	flag_prefix: Option<String>,
+	flag_degree_histogram: Option<String>,
+	flag_path_report: Option<String>,
+	flag_polarization: Option<String>,
+	flag_entropy: Option<String>,
+	flag_entropy_bins: usize,
+	flag_opinion_heatmap: Option<String>,
+	flag_heatmap_bins: usize,
+	flag_summary: Option<String>,
+	flag_neighbor_field: Option<String>,
+	flag_node_table: Option<String>,
+	flag_events: Option<String>,
+	flag_cluster_threshold: f64,
+	flag_init_distribution: InitialDistribution,
+	flag_openness_distribution: OpennessDistribution,
+	flag_init_mean: f64,
+	flag_init_stddev: f64,
+	flag_init_separation: f64,
+	flag_message_stddev: f64,
+	flag_noise_schedule: NoiseSchedule,
+	flag_final_message_stddev: f64,
+	flag_opinion_min: f64,
+	flag_thresholds_relative: bool,
+	flag_opinion_max: f64,
+	flag_boundary: Boundary
 }
 
 
-#[derive(Debug)]
-struct Matrix<T> {
-    size: usize,
-    data: Vec<T>
+// Reject arguments that parsed but make no sense, the same way docopt rejects
+// malformed ones.
+fn invalid(message: &str) -> ! {
+    docopt::Error::Argv(message.to_string()).exit()
 }
 
-impl<T> Matrix<T> where T: Default + Copy + std::fmt::Display + std::cmp::PartialEq {
-    fn new(size: usize) -> Self {
-        Matrix {
-            size: size,
-            data: vec![T::default(); size * size],
+// Parse a TOML file, rejecting it the same way docopt rejects bad arguments.
+fn read_toml(path: &str) -> toml::Table {
+    let mut contents = String::new();
+    if let Err(e) = File::open(path).and_then(|mut file| file.read_to_string(&mut contents)) {
+        invalid(&format!("Unable to read {}: {}", path, e));
+    }
+    let mut parser = toml::Parser::new(&contents);
+    match parser.parse() {
+        Some(table) => table,
+        None => {
+            let error = &parser.errors[0];
+            let (line, column) = parser.to_linecol(error.lo);
+            invalid(&format!("{}:{}:{}: {}", path, line + 1, column + 1, error.desc))
         }
     }
+}
 
-    fn wattz_strogatz(n: usize, k: usize, beta: f64, marker: T) -> Matrix<T> {
-        let mut matrix: Matrix<T> = Matrix::new(n);
+// The file named by --config, if any.
+fn config_path(argv: &[String]) -> Option<String> {
+    for (index, arg) in argv.iter().enumerate() {
+        if arg.starts_with("--config=") {
+            return Some(arg["--config=".len()..].to_string());
+        }
+        if arg == "--config" {
+            return argv.get(index + 1).cloned();
+        }
+    }
+    None
+}
 
-        // Construct a ring lattice.
-        for row in 0..n {
-            let half_k = ((k as f64)/2.0_f64) as usize; 
-            let mut col = matrix.size() - half_k + row;
-            for _ in 0..(k+1) {
+// Splice the parameters from a --config file into the command line, so that
+// docopt parses and validates both the same way. The five positional
+// arguments must come from the file; any other key only applies when the
+// command line does not set that option itself.
+fn with_config(argv: Vec<String>) -> Vec<String> {
+    let path = match config_path(&argv) {
+        Some(path) => path,
+        None => return argv
+    };
 
-                if col > matrix.size() - 1 {
-                    col -= matrix.size();
-                } 
-                if col == row {
-                    col += 1;
-                    continue;
-                }
+    let mut table = read_toml(&path);
 
-                matrix.put(row, col, marker);
-                col += 1;
-            }
+    let value = |key: &str, value: &toml::Value| match *value {
+        toml::Value::String(ref value) => value.clone(),
+        toml::Value::Integer(value) => value.to_string(),
+        toml::Value::Float(value) => value.to_string(),
+        _ => invalid(&format!("{}: {} must be a string or a number.", path, key))
+    };
 
+    let mut merged = vec![argv[0].clone()];
+    for key in &["population", "degree", "rewire", "consensus", "opposition"] {
+        match table.remove(*key) {
+            Some(ref setting) => merged.push(value(key, setting)),
+            None => invalid(&format!("{} must set {}.", path, key))
         }
+    }
+    for (key, setting) in &table {
+        let flag = format!("--{}", key.replace('_', "-"));
+        let given = argv.iter()
+            .any(|arg| *arg == flag || arg.starts_with(&format!("{}=", flag)));
+        if given {
+            continue;
+        }
+        match *setting {
+            toml::Value::Boolean(true) => merged.push(flag),
+            toml::Value::Boolean(false) => {},
+            ref setting => merged.push(format!("{}={}", flag, value(key, setting)))
+        }
+    }
+    merged.extend(argv.into_iter().skip(1));
+    merged
+}
+
+// Report which output could not be written and give up.
+fn fail(path: &str, error: io::Error) -> ! {
+    let _ = writeln!(&mut io::stderr(), "Unable to write {}: {}", path, error);
+    process::exit(1)
+}
+
+/// Where an output's bytes go, optionally through a gzip encoder.
+enum Sink {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>)
+}
+
+impl Sink {
+    // Write out anything still buffered, including the gzip trailer.
+    fn finish(self) -> io::Result<()> {
+        match self {
+            Sink::Plain(mut writer) => writer.flush(),
+            Sink::Gzip(encoder) => encoder.finish().and_then(|mut writer| writer.flush())
+        }
+    }
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match *self {
+            Sink::Plain(ref mut writer) => writer.write(buf),
+            Sink::Gzip(ref mut encoder) => encoder.write(buf)
+        }
+    }
 
-        // Rewire with probability beta. Be sure to symmetically rewire.
-        let mut rng = rand::thread_rng();
-        for row in 0..n {
-            for col in 0..row {
-                let value = matrix.get(row, col);
-                if value == marker && rng.next_f64() <= beta {
-                    for new_col in 0..n {
-                        if new_col == row {
-                            continue;
-                        }
-
-                        if rng.next_f64() <= 1.0_f64/(n as f64) {
-                            matrix.put(row, col, T::default());
-                            matrix.put(col, row, T::default());
-                            matrix.put(row, new_col, marker);
-                            matrix.put(new_col, row, marker);
-                            break;
-                        }
-                    }
-                    
-                }
+    fn flush(&mut self) -> io::Result<()> {
+        match *self {
+            Sink::Plain(ref mut writer) => writer.flush(),
+            Sink::Gzip(ref mut encoder) => encoder.flush()
+        }
+    }
+}
+
+/// An output file, opened before the run and filled in as it goes or once it
+/// is over.
+struct Output {
+    path: String,
+    writer: Sink
+}
+
+impl Output {
+    fn create(path: &str) -> Output {
+        match File::create(path) {
+            Ok(file) => Output {
+                path: path.to_string(),
+                writer: Sink::Plain(BufWriter::new(file))
+            },
+            Err(e) => fail(path, e)
+        }
+    }
+
+    /// Open `<path>.gz` and gzip everything written to it.
+    fn compressed(path: &str) -> Output {
+        let path = format!("{}.gz", path);
+        match File::create(&path) {
+            Ok(file) => Output {
+                writer: Sink::Gzip(GzEncoder::new(BufWriter::new(file), Compression::Default)),
+                path
+            },
+            Err(e) => fail(&path, e)
+        }
+    }
+
+    fn optional(path: &Option<String>) -> Option<Output> {
+        path.as_ref().map(|path| Output::create(path))
+    }
+
+    /// Write the next part of the output.
+    fn append<F>(&mut self, contents: F)
+            where F: FnOnce(&mut Sink) -> io::Result<()> {
+        if let Err(e) = contents(&mut self.writer) {
+            fail(&self.path, e);
+        }
+    }
+
+    fn finish(self) {
+        let Output { path, writer } = self;
+        if let Err(e) = writer.finish() {
+            fail(&path, e);
+        }
+    }
+
+    /// Write the whole output at once.
+    fn write<F>(mut self, contents: F)
+            where F: FnOnce(&mut Sink) -> io::Result<()> {
+        self.append(contents);
+        self.finish();
+    }
+}
+
+// The parameters the metadata file leads with, out of all those
+// `bubbles::parameters` lists.
+const METADATA_PARAMETERS: [&str; 7] = ["population", "degree", "rewire", "consensus",
+                                        "opposition", "seed", "topology"];
+
+fn write_metadata<W: Write>(out: &mut W, config: &SimulationConfig,
+                            result: &SimulationResult, cluster_threshold: f64,
+                            header: bool) -> io::Result<()> {
+    let zealots: Vec<String> = result.stubbornness.iter().enumerate()
+        .filter(|&(_, &stubbornness)| stubbornness == 1.0)
+        .map(|(index, _)| index.to_string())
+        .collect();
+    let openness: Vec<String> = result.openness.iter().map(|v| v.to_string()).collect();
+    let converged_at = result.converged_at.map(|tick| tick.to_string())
+        .unwrap_or_default();
+    // Each surviving cluster as mean:size, with the dimensions of the mean
+    // separated by slashes.
+    let clusters = bubbles::opinion_clusters(&result.opinions, cluster_threshold);
+    let cluster_summary: Vec<String> = clusters.iter()
+        .map(|cluster| {
+            let mean: Vec<String> = cluster.mean.iter().map(|v| v.to_string()).collect();
+            format!("{}:{}", mean.join("/"), cluster.size)
+        })
+        .collect();
+    // Every member's block in a stochastic block model, in member order, and
+    // how cleanly the network splits along them before and after the run.
+    let assignment = if config.topology == Topology::Sbm && config.network.is_none() {
+        bubbles::block_assignment(&config.block_sizes)
+    } else {
+        Vec::new()
+    };
+    let blocks: Vec<String> = assignment.iter().map(|b| b.to_string()).collect();
+    let (initial_modularity, final_modularity) = if assignment.is_empty() {
+        (String::new(), String::new())
+    } else {
+        (initial_network(result).modularity(&assignment).to_string(),
+         result.network.modularity(&assignment).to_string())
+    };
+    // The parameters that set the run apart, named and written as --verbose
+    // prints them.
+    let parameters: BTreeMap<String, String> = bubbles::parameters(config).into_iter().collect();
+    let identity: Vec<&str> = METADATA_PARAMETERS.iter()
+        .map(|&name| parameters[name].as_str())
+        .collect();
+    // Every member's place in a spatial network as x/y, in member order.
+    let positions: Vec<String> = result.positions.iter()
+        .flat_map(|positions| positions.iter())
+        .map(|&(x, y)| format!("{}/{}", x, y))
+        .collect();
+
+    if header {
+        writeln!(out, "{},zealots,converged_at,edge_count,density,\
+                       components,largest_component,clustering_coefficient,\
+                       average_path_length,clusters,cluster_summary,positions,\
+                       openness,blocks,initial_modularity,final_modularity,\
+                       oscillating", METADATA_PARAMETERS.join(","))?;
+    }
+    let stats = &result.initial_stats;
+    writeln!(out, "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+             identity.join(","), zealots.join(" "),
+             converged_at, stats.edge_count, stats.density, stats.components,
+             stats.largest_component, stats.clustering_coefficient,
+             stats.average_path_length, clusters.len(), cluster_summary.join(" "),
+             positions.join(" "), openness.join(" "), blocks.join(" "),
+             initial_modularity, final_modularity, result.oscillating)
+}
+
+// The per-tick writers below each write one tick, with the header before the
+// first.
+
+fn write_network<W: Write>(out: &mut W, state: &TickState, header: bool) -> io::Result<()> {
+    if header {
+        writeln!(out, "tick,sender,recipient,weight")?;
+    }
+    for record in state.records {
+        writeln!(out, "{}, {}, {}, {}", record.tick, record.sender,
+                 record.recipient, record.weight)?;
+    }
+    Ok(())
+}
+
+fn write_opinions<W: Write>(out: &mut W, config: &SimulationConfig, state: &TickState,
+                            format: OpinionFormat, header: bool) -> io::Result<()> {
+    if header && format == OpinionFormat::Wide {
+        let columns: Vec<String> = (0..config.population)
+            .flat_map(|node| (0..config.dimensions).map(move |dimension| (node, dimension)))
+            .map(|(node, dimension)| if config.dimensions == 1 {
+                format!("node_{}", node)
+            } else {
+                format!("node_{}_opinion_{}", node, dimension)
+            })
+            .collect();
+        writeln!(out, "tick,{}", columns.join(","))?;
+    } else if header {
+        if config.dimensions == 1 {
+            writeln!(out, "tick,node,opinion")?;
+        } else {
+            let columns: Vec<String> = (0..config.dimensions)
+                .map(|dimension| format!("opinion_{}", dimension))
+                .collect();
+            writeln!(out, "tick,node,{}", columns.join(","))?;
+        }
+    }
+    if format == OpinionFormat::Wide {
+        let values: Vec<String> = state.opinions.iter().flat_map(|opinion| opinion.iter())
+            .map(|v| v.to_string())
+            .collect();
+        return writeln!(out, "{}, {}", state.tick, values.join(", "));
+    }
+    for (index, opinion) in state.opinions.iter().enumerate() {
+        let values: Vec<String> = opinion.iter().map(|v| v.to_string()).collect();
+        writeln!(out, "{}, {}, {}", state.tick, index, values.join(", "))?;
+    }
+    Ok(())
+}
+
+// Every member's last opinion with its final degree and the mean opinion of
+// the members it is connected to, left empty for a member with no
+// relationships.
+fn write_final_opinions<W: Write>(out: &mut W, config: &SimulationConfig,
+                                  result: &SimulationResult, header: bool) -> io::Result<()> {
+    if header {
+        let columns: Vec<String> = if config.dimensions == 1 {
+            vec!["opinion".to_string(), "degree".to_string(), "neighbor_mean".to_string()]
+        } else {
+            (0..config.dimensions).map(|dimension| format!("opinion_{}", dimension))
+                .chain(Some("degree".to_string()))
+                .chain((0..config.dimensions)
+                    .map(|dimension| format!("neighbor_mean_{}", dimension)))
+                .collect()
+        };
+        writeln!(out, "node,{}", columns.join(","))?;
+    }
+    for (node, opinion) in result.opinions.iter().enumerate() {
+        let neighbors: Vec<Opinion> = result.network.neighbors(node).iter()
+            .map(|&(neighbor, _)| result.opinions[neighbor].clone())
+            .collect();
+        let neighbor_mean: Vec<String> = if neighbors.is_empty() {
+            vec![String::new(); config.dimensions]
+        } else {
+            bubbles::mean_opinion(&neighbors).iter().map(|v| v.to_string()).collect()
+        };
+        let values: Vec<String> = opinion.iter().map(|v| v.to_string()).collect();
+        writeln!(out, "{},{},{},{}", node, values.join(","), result.network.degree(node),
+                 neighbor_mean.join(","))?;
+    }
+    Ok(())
+}
+
+fn write_degree_histogram<W: Write>(out: &mut W, result: &SimulationResult,
+                                    header: bool) -> io::Result<()> {
+    if header {
+        writeln!(out, "degree,count")?;
+    }
+    for &(degree, count) in &result.initial_stats.degree_histogram {
+        writeln!(out, "{},{}", degree, count)?;
+    }
+    Ok(())
+}
+
+// Rebuild the network the run started from out of its first snapshot, in the
+// storage the run used.
+fn initial_network(result: &SimulationResult) -> Network {
+    let mut network = result.network.empty_like();
+    let initial = result.edge_history.iter()
+        .take_while(|record| record.tick == result.first_tick);
+    for record in initial {
+        network.set_weight(record.sender, record.recipient, record.weight);
+    }
+    network
+}
+
+fn write_path_report<W: Write>(out: &mut W, result: &SimulationResult,
+                               header: bool) -> io::Result<()> {
+    if header {
+        writeln!(out, "network,average_weighted_path_length")?;
+    }
+    writeln!(out, "initial,{}", initial_network(result).average_weighted_path_length())?;
+    writeln!(out, "final,{}", result.network.average_weighted_path_length())
+}
+
+// The weighted mean opinion of the members each member hears from. A member
+// nobody is connected to has an empty field.
+fn write_neighbor_field<W: Write>(out: &mut W, config: &SimulationConfig, state: &TickState,
+                                  header: bool) -> io::Result<()> {
+    if header {
+        if config.dimensions == 1 {
+            writeln!(out, "tick,node,field")?;
+        } else {
+            let columns: Vec<String> = (0..config.dimensions)
+                .map(|dimension| format!("field_{}", dimension))
+                .collect();
+            writeln!(out, "tick,node,{}", columns.join(","))?;
+        }
+    }
+    let opinions = state.opinions;
+    for node in 0..opinions.len() {
+        let incoming = state.network.incoming(node);
+        let total: f64 = incoming.iter().map(|&(_, weight)| weight).sum();
+        let field: Vec<String> = if incoming.is_empty() {
+            vec![String::new(); config.dimensions]
+        } else {
+            (0..config.dimensions)
+                .map(|dimension| {
+                    incoming.iter()
+                        .map(|&(neighbor, weight)| weight * opinions[neighbor][dimension])
+                        .sum::<f64>() / total
+                })
+                .map(|value| value.to_string())
+                .collect()
+        };
+        writeln!(out, "{},{},{}", state.tick, node, field.join(","))?;
+    }
+    Ok(())
+}
+
+// Every member's opinion and number of active relationships, laid out as a
+// Gephi node table with dynamic attributes.
+fn write_node_table<W: Write>(out: &mut W, config: &SimulationConfig, state: &TickState,
+                              header: bool) -> io::Result<()> {
+    if header {
+        if config.dimensions == 1 {
+            writeln!(out, "node,tick,opinion,degree")?;
+        } else {
+            let columns: Vec<String> = (0..config.dimensions)
+                .map(|dimension| format!("opinion_{}", dimension))
+                .collect();
+            writeln!(out, "node,tick,{},degree", columns.join(","))?;
+        }
+    }
+    for (node, opinion) in state.opinions.iter().enumerate() {
+        let degree = state.network.neighbors(node).iter()
+            .filter(|&&(_, weight)| weight.abs() >= config.min_active_weight)
+            .count();
+        let values: Vec<String> = opinion.iter().map(|v| v.to_string()).collect();
+        writeln!(out, "{},{},{},{}", node, state.tick, values.join(","), degree)?;
+    }
+    Ok(())
+}
+
+fn write_polarization<W: Write>(out: &mut W, config: &SimulationConfig, state: &TickState,
+                                header: bool) -> io::Result<()> {
+    if header {
+        writeln!(out, "tick,polarization")?;
+    }
+    if state.tick < config.burn_in {
+        return Ok(());
+    }
+    writeln!(out, "{},{}", state.tick, bubbles::polarization(state.opinions))
+}
+
+fn write_entropy<W: Write>(out: &mut W, config: &SimulationConfig, state: &TickState,
+                           bins: usize, header: bool) -> io::Result<()> {
+    if header {
+        writeln!(out, "tick,entropy")?;
+    }
+    if state.tick < config.burn_in {
+        return Ok(());
+    }
+    let entropy = bubbles::opinion_entropy(state.opinions, bins, config.opinion_min,
+                                           config.opinion_max);
+    writeln!(out, "{},{}", state.tick, entropy)
+}
+
+// Every bucket is written, empty or not, so that each tick is a full column
+// of the heatmap.
+fn write_heatmap<W: Write>(out: &mut W, config: &SimulationConfig, state: &TickState,
+                           bins: usize, header: bool) -> io::Result<()> {
+    let several = config.dimensions > 1;
+    if header {
+        writeln!(out, "{}", if several { "tick,dimension,bin,count" } else { "tick,bin,count" })?;
+    }
+    for dimension in 0..config.dimensions {
+        let values: Vec<f64> = state.opinions.iter().map(|opinion| opinion[dimension]).collect();
+        let counts = bubbles::histogram(&values, bins, config.opinion_min, config.opinion_max);
+        for (bin, count) in counts.into_iter().enumerate() {
+            if several {
+                writeln!(out, "{},{},{},{}", state.tick, dimension, bin, count)?;
+            } else {
+                writeln!(out, "{},{},{}", state.tick, bin, count)?;
             }
         }
+    }
+    Ok(())
+}
+
+fn write_summary<W: Write>(out: &mut W, config: &SimulationConfig, state: &TickState,
+                           header: bool) -> io::Result<()> {
+    if header {
+        writeln!(out, "tick,mean,variance,min,max,edges")?;
+    }
+    let summary = state.summary;
+    if summary.tick < config.burn_in {
+        return Ok(());
+    }
+    writeln!(out, "{},{},{},{},{},{}", summary.tick, summary.mean, summary.variance,
+             summary.min, summary.max, summary.edges)
+}
 
-        return matrix;
+fn write_events<W: Write>(out: &mut W, result: &SimulationResult,
+                          header: bool) -> io::Result<()> {
+    if header {
+        writeln!(out, "tick,type,sender,recipient")?;
+    }
+    for event in &result.events {
+        let kind = match event.kind {
+            EdgeEventKind::Formed => "formed",
+            EdgeEventKind::Removed => "removed"
+        };
+        writeln!(out, "{},{},{},{}", event.tick, kind, event.sender, event.recipient)?;
+    }
+    Ok(())
+}
 
+// A number in a TOML file, which may be written with or without a fraction.
+fn toml_number(value: &toml::Value) -> Option<f64> {
+    match *value {
+        toml::Value::Integer(value) => Some(value as f64),
+        toml::Value::Float(value) => Some(value),
+        _ => None
     }
+}
 
-    fn print(&self) {
-        for row in 0..self.size() {
-            for col in 0..self.size() {
-                let value: T = self.get(row, col);
-                print!("{} ", value);
+// The values a sweep takes for one parameter: an explicit list, a
+// start/stop/step range that includes stop, or just `default` when the
+// parameter is not swept.
+fn sweep_values(path: &str, key: &str, table: &toml::Table, default: f64) -> Vec<f64> {
+    let malformed = || -> ! {
+        invalid(&format!("{}: {} must be a list of numbers or a table of start, stop and \
+                          step.", path, key))
+    };
+    match table.get(key) {
+        None => vec![default],
+        Some(&toml::Value::Array(ref values)) => {
+            values.iter().map(|value| toml_number(value).unwrap_or_else(|| malformed())).collect()
+        },
+        Some(&toml::Value::Table(ref range)) => {
+            let field = |name: &str| {
+                range.get(name).and_then(toml_number).unwrap_or_else(|| malformed())
+            };
+            let (start, stop, step) = (field("start"), field("stop"), field("step"));
+            if step <= 0.0 || stop < start {
+                invalid(&format!("{}: {} needs a positive step and stop no smaller than \
+                                  start.", path, key));
+            }
+            // Allow for rounding so that stop itself is included.
+            let count = ((stop - start) / step + 1e-9).floor() as usize + 1;
+            (0..count).map(|index| start + index as f64 * step).collect()
+        },
+        Some(_) => malformed()
+    }
+}
+
+/// The outcome of one run of a sweep.
+struct SweepRow {
+    config: SimulationConfig,
+    polarization: f64,
+    clusters: usize,
+    converged_at: Option<usize>,
+    oscillating: bool
+}
+
+// Run every combination of rewire, consensus and opposition in the sweep file
+// in parallel. Every run shares the base seed, so runs differ only in their
+// parameters. The file's consensus and opposition values are multiplied by
+// `threshold_scale`, as the command line ones were.
+fn sweep(path: &str, base: &SimulationConfig, threshold_scale: f64,
+         cluster_threshold: f64) -> Vec<SweepRow> {
+    let table = read_toml(path);
+    let mut configs = Vec::new();
+    let rewires = sweep_values(path, "rewire", &table, base.rewire);
+    if rewires.iter().any(|&rewire| !(rewire >= 0.0 && rewire <= 1.0)) {
+        invalid(&format!("{}: rewire is a probability and must be between 0 and 1.", path));
+    }
+    let thresholds = |key: &str, default: f64| -> Vec<f64> {
+        match table.get(key) {
+            None => vec![default],
+            Some(_) => sweep_values(path, key, &table, default).into_iter()
+                .map(|value| value * threshold_scale)
+                .collect()
+        }
+    };
+    for &rewire in &rewires {
+        for &consensus in &thresholds("consensus", base.consensus) {
+            for &opposition in &thresholds("opposition", base.opposition) {
+                let mut config = base.clone();
+                config.record_history = false;
+                config.rewire = rewire;
+                config.consensus = consensus;
+                config.opposition = opposition;
+                configs.push(config);
             }
-            print!("\n");
         }
     }
 
-    fn size(&self) -> usize {
-        self.size
+    configs.into_par_iter()
+        .map(|config| {
+            let result = bubbles::simulate(&config);
+            SweepRow {
+                polarization: bubbles::polarization(&result.opinions),
+                clusters: bubbles::opinion_clusters(&result.opinions, cluster_threshold).len(),
+                converged_at: result.converged_at,
+                oscillating: result.oscillating,
+                config
+            }
+        })
+        .collect()
+}
+
+fn write_sweep<W: Write>(out: &mut W, rows: &[SweepRow], header: bool) -> io::Result<()> {
+    if header {
+        writeln!(out, "rewire,consensus,opposition,seed,polarization,clusters,converged_at,\
+                     oscillating")?;
+    }
+    for row in rows {
+        let converged_at = row.converged_at.map(|tick| tick.to_string()).unwrap_or_default();
+        writeln!(out, "{},{},{},{},{},{},{},{}", row.config.rewire, row.config.consensus,
+                 row.config.opposition, row.config.seed, row.polarization, row.clusters,
+                 converged_at, row.oscillating)?;
     }
+    Ok(())
+}
 
-    fn index_for(&self, row: usize, col: usize) -> usize {
-        row * self.size + col
+// The mean and sample standard deviation of `values`. A single value has no
+// spread.
+fn mean_and_stddev(values: &[f64]) -> (f64, f64) {
+    let mean = bubbles::mean(values);
+    if values.len() < 2 {
+        return (mean, 0.0);
     }
+    let count = values.len() as f64;
+    (mean, (bubbles::variance(values) * count / (count - 1.0)).sqrt())
+}
+
+/// How the outcome of a parameter set varies from run to run.
+struct RepeatSummary {
+    seed: u64,
+    repeats: usize,
+    metrics: Vec<(&'static str, f64, f64)>,
+    /// Each run's seed with its final polarization, cluster count and
+    /// consensus fraction, in run order.
+    replicates: Vec<(u64, (f64, f64, f64))>
+}
 
-    fn get(&self, row: usize, col: usize) -> T {
-        let index = self.index_for(row, col);
-        self.data[index]
+// The seeds for `repeats` runs, drawn from a generator seeded with `seed` so
+// that the whole set is reproducible.
+fn repeat_seeds(seed: u64, repeats: usize) -> Vec<u64> {
+    let mut rng: StdRng = SeedableRng::from_seed(&[seed as usize][..]);
+    (0..repeats).map(|_| rng.gen::<u64>()).collect()
+}
+
+// Run `base` once per seed, in parallel.
+fn repeat(base: &SimulationConfig, seeds: &[u64], cluster_threshold: f64) -> RepeatSummary {
+    let configs: Vec<SimulationConfig> = seeds.iter()
+        .map(|&seed| {
+            let mut config = base.clone();
+            config.record_history = false;
+            config.seed = seed;
+            config
+        })
+        .collect();
+
+    let outcomes: Vec<(f64, f64, f64)> = configs.into_par_iter()
+        .map(|config| {
+            let result = bubbles::simulate(&config);
+            let clusters = bubbles::opinion_clusters(&result.opinions, cluster_threshold);
+            let largest = clusters.iter().map(|cluster| cluster.size).max().unwrap_or(0);
+            (bubbles::polarization(&result.opinions), clusters.len() as f64,
+             largest as f64 / config.population as f64)
+        })
+        .collect();
+
+    let column = |pick: fn(&(f64, f64, f64)) -> f64| {
+        mean_and_stddev(&outcomes.iter().map(pick).collect::<Vec<f64>>())
+    };
+    let (polarization, clusters, consensus) = (column(|o| o.0), column(|o| o.1),
+                                               column(|o| o.2));
+    RepeatSummary {
+        seed: base.seed,
+        repeats: seeds.len(),
+        metrics: vec![("polarization", polarization.0, polarization.1),
+                      ("clusters", clusters.0, clusters.1),
+                      ("consensus_fraction", consensus.0, consensus.1)],
+        replicates: seeds.iter().cloned().zip(outcomes).collect()
     }
+}
 
-    fn put(&mut self, row: usize, col: usize, value: T) {
-        let index = self.index_for(row, col);
-        self.data[index] = value;
-        let index2 = self.index_for(col,row);
-        self.data[index2] = value;
+fn write_repeats<W: Write>(out: &mut W, summary: &RepeatSummary,
+                           header: bool) -> io::Result<()> {
+    if header {
+        writeln!(out, "seed,repeats,metric,mean,stddev")?;
+    }
+    for &(metric, mean, stddev) in &summary.metrics {
+        writeln!(out, "{},{},{},{},{}", summary.seed, summary.repeats, metric, mean, stddev)?;
     }
+    Ok(())
 }
 
-fn main() {
+// One row per run, so that any single replicate can be rerun with --seed.
+fn write_replicates<W: Write>(out: &mut W, summary: &RepeatSummary,
+                              header: bool) -> io::Result<()> {
+    if header {
+        writeln!(out, "replicate,seed,polarization,clusters,consensus_fraction")?;
+    }
+    for (replicate, &(seed, (polarization, clusters, consensus))) in
+            summary.replicates.iter().enumerate() {
+        writeln!(out, "{},{},{},{},{}", replicate, seed, polarization, clusters, consensus)?;
+    }
+    Ok(())
+}
 
-    let args: Args = Docopt::new(USAGE)
-                            .and_then(|d| d.decode())
-                            .unwrap_or_else(|e| e.exit());
+// Print every frame of a network CSV: the relationships as they stood once
+// all the rows for a tick were applied. An undirected run may record a tie
+// from either end, so both ends share one entry.
+fn write_frames<W: Write, R: BufRead>(out: &mut W, path: &str, reader: R, directed: bool,
+                                      header: bool) -> io::Result<()> {
+    if header {
+        writeln!(out, "frame,tick,sender,recipient,weight")?;
+    }
+    let write_frame = |out: &mut W, frame: usize, tick: usize,
+                       weights: &BTreeMap<(usize, usize), f64>| -> io::Result<()> {
+        for (&(sender, recipient), weight) in weights {
+            writeln!(out, "{},{},{},{},{}", frame, tick, sender, recipient, weight)?;
+        }
+        Ok(())
+    };
 
-    let population: usize = args.arg_population;
-    let mut rng = rand::thread_rng();
-	let max_time = 10000;
+    let mut weights: BTreeMap<(usize, usize), f64> = BTreeMap::new();
+    let mut current: Option<usize> = None;
+    let mut frame = 0;
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(',').map(|field| field.trim()).collect();
+        let record = if fields.len() == 4 {
+            match (fields[0].parse::<usize>(), fields[1].parse::<usize>(),
+                   fields[2].parse::<usize>(), fields[3].parse::<f64>()) {
+                (Ok(tick), Ok(sender), Ok(recipient), Ok(weight)) => {
+                    Some((tick, sender, recipient, weight))
+                },
+                _ => None
+            }
+        } else {
+            None
+        };
+        let (tick, sender, recipient, weight) = match record {
+            Some(record) => record,
+            None if index == 0 => continue,
+            None => {
+                return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                          format!("{}:{}: expected tick,sender,recipient,\
+                                                   weight, found {:?}", path, index + 1, line)))
+            }
+        };
 
+        if let Some(previous) = current {
+            if tick != previous {
+                write_frame(out, frame, previous, &weights)?;
+                frame += 1;
+            }
+        }
+        current = Some(tick);
+        let key = if directed || sender > recipient {
+            (sender, recipient)
+        } else {
+            (recipient, sender)
+        };
+        if weight == 0.0 {
+            weights.remove(&key);
+        } else {
+            weights.insert(key, weight);
+        }
+    }
+    if let Some(tick) = current {
+        write_frame(out, frame, tick, &weights)?;
+    }
+    Ok(())
+}
 
-    let consensus = args.arg_consensus;
-	let irreconsilable = args.arg_opposition;
+fn replay(path: &str, directed: bool, header: bool) {
+    let file = File::open(path)
+        .unwrap_or_else(|e| invalid(&format!("Unable to read --replay: {}", e)));
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    let result = if path.ends_with(".gz") {
+        GzDecoder::new(file)
+            .and_then(|decoder| write_frames(&mut out, path, BufReader::new(decoder), directed,
+                                             header))
+    } else {
+        write_frames(&mut out, path, BufReader::new(file), directed, header)
+    };
+    if let Err(e) = result.and_then(|_| out.flush()) {
+        invalid(&format!("Unable to replay {}: {}", path, e));
+    }
+}
+
+fn main() {
+
+    let argv = with_config(env::args().collect());
+    let parsed = Docopt::new(USAGE)
+                        .and_then(|d| d.argv(argv.into_iter()).parse())
+                        .unwrap_or_else(|e| e.exit());
+    // Replaying needs none of the simulation's parameters.
+    if !parsed.get_str("--replay").is_empty() {
+        replay(parsed.get_str("--replay"), parsed.get_bool("--directed"),
+               !parsed.get_bool("--no-header"));
+        return;
+    }
+    let args: Args = parsed.decode().unwrap_or_else(|e| e.exit());
+
+    if args.flag_ticks == 0 {
+        invalid("--ticks must be greater than zero.");
+    }
+    if args.arg_population < 2 {
+        invalid("<population> must be at least 2.");
+    }
+    // Written so that NaN fails as well.
+    if !(args.arg_rewire >= 0.0 && args.arg_rewire <= 1.0) {
+        invalid("<rewire> is a probability and must be between 0 and 1.");
+    }
+    if !(args.arg_consensus >= 0.0 && args.arg_opposition >= 0.0) {
+        invalid("<consensus> and <opposition> must not be negative.");
+    }
+    if !(args.flag_zealots >= 0.0 && args.flag_zealots <= 1.0) {
+        invalid("--zealots is a fraction and must be between 0 and 1.");
+    }
+    // Generated networks cannot honour a degree the population cannot hold.
+    if args.flag_network_input.is_none() {
+        let (population, degree) = (args.arg_population, args.arg_degree);
+        match args.flag_topology {
+            Topology::Ws | Topology::Ring if degree >= population => {
+                invalid("--topology ws and ring need <degree> to be smaller than \
+                         <population>.")
+            },
+            Topology::Ws | Topology::Ring if degree % 2 == 1 => {
+                invalid("--topology ws and ring join each member to <degree>/2 members on \
+                         either side, so <degree> must be even.")
+            },
+            Topology::Ba if degree < 2 || degree / 2 >= population => {
+                invalid("--topology ba attaches <degree>/2 edges per member, so <degree> \
+                         must be at least 2 and <degree>/2 smaller than <population>.")
+            },
+            Topology::Er if degree > population - 1 => {
+                invalid("--topology er needs <degree> to be at most <population> - 1.")
+            },
+            Topology::Sbm if args.flag_block_sizes.is_none() => {
+                invalid("--topology sbm needs --block-sizes.")
+            },
+            _ => {}
+        }
+    }
+    if args.flag_entropy_bins == 0 {
+        invalid("--entropy-bins must be greater than zero.");
+    }
+    if args.flag_heatmap_bins == 0 {
+        invalid("--heatmap-bins must be greater than zero.");
+    }
+    if args.flag_opinion_sample_interval == Some(0) {
+        invalid("--opinion-sample-interval must be greater than zero.");
+    }
+    if !(args.flag_radius >= 0.0) {
+        invalid("--radius must not be negative.");
+    }
+    // A zero weight is no relationship at all.
+    if !(args.flag_initial_weight > 0.0 && args.flag_initial_weight <= 1.0) {
+        invalid("--initial-weight must be greater than 0 and at most 1.");
+    }
+    if args.flag_randomize_weights && args.flag_homophilous_init {
+        invalid("--randomize-weights and --homophilous-init cannot be combined.");
+    }
+    if !(args.flag_p_in >= 0.0 && args.flag_p_in <= 1.0 &&
+         args.flag_p_out >= 0.0 && args.flag_p_out <= 1.0) {
+        invalid("--p-in and --p-out are probabilities and must be between 0 and 1.");
+    }
+    if args.flag_threads == Some(0) {
+        invalid("--threads must be greater than zero.");
+    }
+    if args.flag_messages_per_tick == 0 {
+        invalid("--messages-per-tick must be greater than zero.");
+    }
+    if args.flag_directed && args.flag_sparse {
+        invalid("--directed networks are always stored densely; drop --sparse.");
+    }
+    if args.flag_assimilation_rate < 0.0 {
+        invalid("--assimilation-rate must not be negative.");
+    }
+    if args.flag_opinion_max <= args.flag_opinion_min {
+        invalid("--opinion-max must be greater than --opinion-min.");
+    }
+    // Truncating the initial draws to the range would otherwise pile everyone
+    // up at its edge.
+    let in_range = |opinion: f64| {
+        opinion >= args.flag_opinion_min && opinion <= args.flag_opinion_max
+    };
+    if !in_range(args.flag_init_mean) {
+        invalid("--init-mean must lie between --opinion-min and --opinion-max.");
+    }
+    if args.flag_media_opinion.is_some_and(|opinion| !in_range(opinion)) {
+        invalid("--media-opinion must lie between --opinion-min and --opinion-max.");
+    }
+    if args.flag_grudges && !args.flag_directed {
+        invalid("--grudges needs the two directions of a tie to differ; add --directed.");
+    }
+    if args.flag_mutual && args.flag_directed {
+        invalid("--mutual exchanges need shared ties; drop --directed.");
+    }
+    if args.flag_mutual && args.flag_model != Model::Threshold {
+        invalid("--mutual only applies to the threshold model.");
+    }
+    if !(args.flag_inertia >= 0.0 && args.flag_inertia <= 1.0) {
+        invalid("--inertia is a share and must be between 0 and 1.");
+    }
+    if args.flag_no_backfire && args.flag_model != Model::Threshold {
+        invalid("--no-backfire only applies to the threshold model.");
+    }
+    if !(args.flag_min_active_weight >= 0.0 && args.flag_min_active_weight <= 1.0) {
+        invalid("--min-active-weight must be between 0 and 1.");
+    }
+    if args.flag_min_active_weight > 0.0 && args.flag_model == Model::Hk {
+        invalid("--min-active-weight needs an asynchronous model; hk never changes relationships.");
+    }
+    if args.flag_signed && args.flag_model == Model::Hk {
+        invalid("--signed needs an asynchronous model; hk has no notion of an enemy.");
+    }
+    if args.flag_media_opinion.is_some() && args.flag_model == Model::Hk {
+        invalid("--media-opinion needs an asynchronous model; hk updates everyone at once.");
+    }
+    if !(args.flag_media_reach >= 0.0 && args.flag_media_reach <= 1.0) {
+        invalid("--media-reach must be between 0 and 1.");
+    }
+    if args.flag_repeats == Some(0) {
+        invalid("--repeats must be greater than zero.");
+    }
+    let seeds: Option<Vec<u64>> = args.flag_seeds.as_ref().map(|seeds| {
+        let seeds: Result<Vec<u64>, _> = seeds.split(',').map(|seed| seed.trim().parse())
+            .collect();
+        match seeds {
+            Ok(seeds) => seeds,
+            Err(_) => invalid("--seeds must be a comma-separated list of seeds.")
+        }
+    });
+    if let (Some(ref seeds), Some(repeats)) = (seeds.as_ref(), args.flag_repeats) {
+        if seeds.len() != repeats {
+            invalid("--repeats must match the number of --seeds.");
+        }
+    }
+    let repeats = seeds.as_ref().map(|seeds| seeds.len()).or(args.flag_repeats);
+    if repeats.is_some() && args.flag_sweep.is_some() {
+        invalid("--repeats and --sweep each write their own summary; pick one.");
+    }
+    if args.flag_checkpoint_interval == Some(0) {
+        invalid("--checkpoint-interval must be greater than zero.");
+    }
+    let single_run = args.flag_checkpoint_interval.is_some() || args.flag_resume.is_some();
+    if single_run && (args.flag_sweep.is_some() || repeats.is_some()) {
+        invalid("--checkpoint-interval and --resume apply to a single run; drop --sweep and \
+                 --repeats.");
+    }
+    if args.flag_init_stddev < 0.0 || args.flag_message_stddev < 0.0 {
+        invalid("--init-stddev and --message-stddev must not be negative.");
+    }
+    if !(args.flag_final_message_stddev >= 0.0) {
+        invalid("--final-message-stddev must not be negative.");
+    }
+    if args.flag_noise_schedule == NoiseSchedule::Exponential &&
+            !(args.flag_message_stddev > 0.0 && args.flag_final_message_stddev > 0.0) {
+        invalid("--noise-schedule exponential needs --message-stddev and \
+                 --final-message-stddev to be greater than zero.");
+    }
+
+    // Seed a single generator so that a run can be replayed exactly.
+    let seed: u64 = args.flag_seed.unwrap_or_else(|| rand::random::<u64>());
+    let threshold_scale = if args.flag_thresholds_relative {
+        args.flag_opinion_max - args.flag_opinion_min
+    } else {
+        1.0
+    };
+    let mut config = SimulationConfig::new(args.arg_population, args.arg_degree,
+                                           args.arg_rewire,
+                                           args.arg_consensus * threshold_scale,
+                                           args.arg_opposition * threshold_scale, seed);
+    config.topology = args.flag_topology;
+    config.radius = args.flag_radius;
+    config.initial_weight = args.flag_initial_weight;
+    config.randomize_weights = args.flag_randomize_weights;
+    config.homophilous_init = args.flag_homophilous_init;
+    if let Some(ref sizes) = args.flag_block_sizes {
+        let sizes: Result<Vec<usize>, _> = sizes.split(',').map(|size| size.trim().parse())
+            .collect();
+        match sizes {
+            Ok(ref sizes) if sizes.iter().sum::<usize>() == args.arg_population &&
+                             !sizes.contains(&0) => config.block_sizes = sizes.clone(),
+            _ => invalid("--block-sizes must be a comma-separated list of positive sizes \
+                          adding up to <population>.")
+        }
+    }
+    config.p_in = args.flag_p_in;
+    config.p_out = args.flag_p_out;
+    if let Some(ref path) = args.flag_network_input {
+        // Load straight into the storage the run uses, so that a sparse run
+        // never holds a dense matrix. A directed run reads the relationships
+        // as two-way ties, as it does for a generated network.
+        let (n, weight) = (args.arg_population, args.flag_initial_weight);
+        let loaded: io::Result<Network> = if args.flag_directed {
+            Matrix::from_edge_list(path, n, weight)
+                .map(|matrix| DirectedMatrix::from(matrix).into())
+        } else if args.flag_sparse {
+            SparseMatrix::from_edge_list(path, n, weight).map(Network::from)
+        } else {
+            Matrix::from_edge_list(path, n, weight).map(Network::from)
+        };
+        let network = loaded.unwrap_or_else(|e| {
+            invalid(&format!("Unable to load --network-input: {}", e))
+        });
+        let floor = if args.flag_signed { -1.0 } else { 0.0 };
+        let outside = network.edges().into_iter()
+            .find(|edge| !(edge.weight >= floor && edge.weight <= 1.0));
+        if let Some(edge) = outside {
+            invalid(&format!("--network-input relationship {},{} has weight {}, outside \
+                              [{}, 1].", edge.sender, edge.recipient, edge.weight, floor));
+        }
+        config.network = Some(network);
+    }
+    config.sparse = args.flag_sparse;
+    config.directed = args.flag_directed;
+    config.grudges = args.flag_grudges;
+    config.signed = args.flag_signed;
+    config.mutual = args.flag_mutual;
+    // Every other per-tick output is written as the run goes.
+    config.record_history = args.flag_format == Format::Json || args.flag_export_gexf.is_some();
+    config.full_network_dump = args.flag_full_network_dump;
+    config.model = args.flag_model;
+    config.confidence_bound = ConfidenceBound(args.flag_confidence_bound);
+    config.convergence_rate = ConvergenceRate(args.flag_convergence_rate);
+    config.dimensions = args.flag_dimensions;
+    config.opinion_min = args.flag_opinion_min;
+    config.opinion_max = args.flag_opinion_max;
+    config.boundary = args.flag_boundary;
+    if let Some(ref path) = args.flag_opinions_input {
+        match bubbles::read_opinions(path, config.population, config.dimensions,
+                                     config.opinion_min, config.opinion_max) {
+            Ok(opinions) => config.opinions = Some(opinions),
+            Err(e) => invalid(&format!("Unable to load --opinions-input: {}", e))
+        }
+    }
+    config.zealots = args.flag_zealots;
+    config.media = args.flag_media_opinion.map(|opinion| Media {
+        opinion,
+        reach: args.flag_media_reach,
+        strength: args.flag_media_strength
+    });
+    config.neutral_drift = args.flag_neutral_drift;
+    config.assimilation_rate = args.flag_assimilation_rate;
+    config.backfire = !args.flag_no_backfire;
+    config.inertia = args.flag_inertia;
+    config.init_distribution = args.flag_init_distribution;
+    config.openness_distribution = args.flag_openness_distribution;
+    config.init_mean = args.flag_init_mean;
+    config.init_stddev = args.flag_init_stddev;
+    config.init_separation = args.flag_init_separation;
+    config.message_stddev = args.flag_message_stddev;
+    config.noise_schedule = args.flag_noise_schedule;
+    config.final_message_stddev = args.flag_final_message_stddev;
+    config.ticks = args.flag_ticks;
+    config.messages_per_tick = args.flag_messages_per_tick;
+    config.reach_weighting = args.flag_reach_weighting;
+    config.rewire_on_conflict = args.flag_rewire_on_conflict;
+    config.min_active_weight = args.flag_min_active_weight;
+    config.threads = args.flag_threads;
+    config.convergence_epsilon = args.flag_convergence_epsilon;
+    config.stable_window = args.flag_stable_window;
+    config.burn_in = args.flag_burn_in;
+    config.checkpoint_interval = args.flag_checkpoint_interval;
+    if let Some(ref path) = args.flag_resume {
+        let checkpoint = Checkpoint::from_file(path)
+            .unwrap_or_else(|e| invalid(&format!("Unable to load --resume: {}", e)));
+        let dimensions = checkpoint.opinions.first().map_or(0, |opinion| opinion.len());
+        if checkpoint.opinions.len() != config.population || dimensions != config.dimensions ||
+                checkpoint.directed != config.directed {
+            invalid(&format!("{} was written by a run with a different population, number \
+                              of dimensions or --directed.", path));
+        }
+        if checkpoint.tick >= config.ticks {
+            invalid(&format!("{} is already at tick {}; raise --ticks to carry on.", path,
+                             checkpoint.tick));
+        }
+        config.resume = Some(checkpoint);
+    }
 
-    // Open the opinions file.
     let utc: DateTime<UTC> = UTC::now();
     let date_string = utc.format("%Y-%m-%dT%H:%M:%SZ").to_string();
 
-    let mut opinion_file = File::create("simulation_".to_string() + &date_string + ".csv")
-                            .expect("Unable to create file.");
-    let mut network_file = File::create("network_".to_string() + &date_string + ".csv")
-                            .expect("Unable to create file.");
-    let mut metadata_file = File::create("metadata_".to_string() + &date_string + ".csv")
-                            .expect("Unable to create file.");
-
-    write!(metadata_file, "{},{},{},{},{}\n", args.arg_population,
-	       args.arg_degree,	args.arg_rewire, args.arg_consensus, 
-           args.arg_opposition);
-
-
-
-    // Generate the network
-    let mut social_network: Matrix<f64> =  Matrix::wattz_strogatz(population,
-                                                                  args.arg_degree, 
-                                                                  args.arg_rewire,
-                                                                  0.5_f64);
-   
-	// Initilize opinions
-	let opinion_distribution = Normal::new(50.0, 10.0);
-	let mut opinions: Vec<f64> = Vec::new();
-	for _ in 0..population {
-		opinions.push(opinion_distribution.ind_sample(&mut rng).abs());
-	}
-
-    // Store the initial state of the matrix
-    for sender in 0..population {
-        for recipient in 0..sender {
-            let weight = social_network.get(sender, recipient);
-            if sender == recipient || weight == 0.0_f64 {
-                continue
+    let output_dir = Path::new(args.flag_output_dir.as_ref().map_or(".", |dir| dir.as_str()));
+    // Name files after the run when a prefix is given, otherwise after the
+    // time the run started.
+    let output_path = |kind: &str, extension: &str| {
+        let name = match args.flag_prefix {
+            Some(ref prefix) => format!("{}_{}.{}", prefix, kind, extension),
+            None => format!("{}_{}.{}", kind, date_string, extension)
+        };
+        output_dir.join(name).to_string_lossy().into_owned()
+    };
+    config.checkpoint_path = output_path("checkpoint", "txt");
+
+    if args.flag_verbose {
+        let _ = bubbles::write_parameters(&mut io::stderr(), &config);
+    }
+    if args.flag_dry_run {
+        let stats = bubbles::build_network(&config).stats();
+        println!("edge_count: {}", stats.edge_count);
+        println!("density: {}", stats.density);
+        println!("components: {}", stats.components);
+        println!("largest_component: {}", stats.largest_component);
+        println!("clustering_coefficient: {}", stats.clustering_coefficient);
+        println!("average_path_length: {}", stats.average_path_length);
+        println!("degree,count");
+        for &(degree, count) in &stats.degree_histogram {
+            println!("{},{}", degree, count);
+        }
+        return;
+    }
+
+    // Open the output files up front so a bad location fails before the run.
+    fs::create_dir_all(output_dir)
+        .unwrap_or_else(|e| fail(&output_dir.to_string_lossy(), e));
+
+    // A sweep replaces the usual outputs with one summary row per run, and
+    // repeats with one row per metric.
+    if let Some(ref path) = args.flag_sweep {
+        let sweep_file = Output::create(&output_path("sweep", "csv"));
+        let rows = sweep(path, &config, threshold_scale, args.flag_cluster_threshold);
+        sweep_file.write(|out| write_sweep(out, &rows, !args.flag_no_header));
+        return;
+    }
+    if let Some(repeats) = repeats {
+        let repeats_file = Output::create(&output_path("repeats", "csv"));
+        let replicates_file = Output::create(&output_path("replicates", "csv"));
+        let seeds = seeds.unwrap_or_else(|| repeat_seeds(config.seed, repeats));
+        let summary = repeat(&config, &seeds, args.flag_cluster_threshold);
+        repeats_file.write(|out| write_repeats(out, &summary, !args.flag_no_header));
+        replicates_file.write(|out| write_replicates(out, &summary, !args.flag_no_header));
+        return;
+    }
+
+    // Only the outputs that grow with the run are worth compressing.
+    let bulky = |path: String| {
+        if args.flag_compress { Output::compressed(&path) } else { Output::create(&path) }
+    };
+    let (mut opinion_file, mut network_file, metadata_file, final_file) = match args.flag_format {
+        Format::Csv => (Some(bulky(output_path("simulation", "csv"))),
+                        Some(bulky(output_path("network", "csv"))),
+                        Some(Output::create(&output_path("metadata", "csv"))),
+                        Some(Output::create(&output_path("final_opinions", "csv")))),
+        Format::Json | Format::Jsonl => (None, None, None, None)
+    };
+    let json_file = match args.flag_format {
+        Format::Json => Some(bulky(output_path("simulation", "json"))),
+        Format::Csv | Format::Jsonl => None
+    };
+    let graphml_file = Output::optional(&args.flag_export_graphml);
+    let gexf_file = Output::optional(&args.flag_export_gexf);
+    let matrix_file = Output::optional(&args.flag_export_matrix);
+    let histogram_file = Output::optional(&args.flag_degree_histogram);
+    let path_file = Output::optional(&args.flag_path_report);
+    let mut polarization_file = Output::optional(&args.flag_polarization);
+    let mut entropy_file = Output::optional(&args.flag_entropy);
+    let mut heatmap_file = Output::optional(&args.flag_opinion_heatmap);
+    let mut summary_file = Output::optional(&args.flag_summary);
+    let mut field_file = Output::optional(&args.flag_neighbor_field);
+    let mut node_file = Output::optional(&args.flag_node_table);
+    let events_file = Output::optional(&args.flag_events);
+
+    // Report roughly every 1% of the run, and at least every tick of a short
+    // one.
+    let report_every = std::cmp::max(config.ticks / 100, 1);
+    let progress = args.flag_progress;
+    let stream = args.flag_format == Format::Jsonl;
+    let stdout = io::stdout();
+    let header = !args.flag_no_header;
+    let interval = args.flag_opinion_sample_interval;
+    let first_tick = config.resume.as_ref().map_or(0, |checkpoint| checkpoint.tick);
+    let result = bubbles::simulate_observed(&config, |state| {
+        let tick = state.tick;
+        if tick > first_tick {
+            // Flush every line so that a consumer sees each tick as it ends.
+            if stream {
+                let mut out = stdout.lock();
+                let _ = bubbles::write_tick_json(&mut out, tick, state.opinions)
+                    .and_then(|_| out.flush());
             }
-            write!(network_file, "0, {}, {}, {}\n", sender, recipient, weight);
-        }
-    }
-
-
-	// Simulation loop
-	// Here are the rule, every tick, we'll randomly pick a vertex and send a
-	// message to it's neighbors. The opinion of the message will reflect the
-	// opinions of the sender. Upon receiving the message, alter the reciever's
-	// opinion by some percent of the difference in opinion.
-	for tick in 1..max_time {
-		
-		let sender = rng.gen_range(0, population);
-		let message_distribution = Normal::new(opinions[sender], 10.0);
-		let message = message_distribution.ind_sample(&mut rng); 
-
-		for recipient in 0..population {
-			if social_network.get(sender, recipient) <= 0.0 {
-				continue
-			}
-
-			// Adjust opinions
-            let opinion_change =  social_network.get(sender, recipient) * 
-									  ((message - opinions[recipient])/ 100.0);
-
-
-			// Adjust social standing due to message. We're going to split this
-			// into three categories.
-			//	1. Consensus: Within 25% of each other. Increase relationship.
-			//  2. Challenged: Within 75% of each other. Do nothing.
-			//  3. Irreconsilable: More that 75% different. Decrease relationship
-			let difference = (message - opinions[recipient]).abs(); 
-			if difference < consensus {
-				let strength = social_network.get(sender, recipient);
-				let mut new_strength = strength + (consensus - difference)/100.0;
-				if new_strength > 1.0 {
-					new_strength = 1.0
-				}
-              
-
-                // Adjust opinion so that the person's opinion is more in line
-                // with the message.
-                if message < opinions[recipient] {
-                    opinions[recipient] -=  opinion_change.abs();
-                } else {
-                    opinions[recipient] +=  opinion_change.abs();
-                }
-
-
-				social_network.put(sender, 
-								   recipient, 
-								   new_strength);
-
-		    } else if difference > irreconsilable {
-				let strength = social_network.get(sender, recipient);
-				let mut new_strength = strength - (difference - irreconsilable)/100.0;
-				if new_strength < 0.0 {
-					new_strength = 0.0
-				}
-				social_network.put(sender, 
-								   recipient, 
-								   new_strength);
-
-
-               // Adjust opinion so that the person's opinion moves away from
-               // the message.
-               
-                if message < opinions[recipient] {
-                    opinions[recipient] +=  opinion_change.abs();
-                } else {
-                    opinions[recipient] -=  opinion_change.abs();
-                }
-
-
-			}
-            
-            write!(network_file, "{}, {}, {}, {}\n", tick, sender, 
-                   recipient, social_network.get(sender, recipient));
-
-		}
-
-		// Cleanup opinions to be within [0, 100]
-		for index in 0..population {
-			if opinions[index] < 0.0 {
-				opinions[index] = 0.0;
-			} else if opinions[index] > 100.0 {
-				opinions[index] = 100.0;
-			}
-		}
-
-
-		for index in 0..population {
-            write!(opinion_file, "{}, {}, {}\n", tick, index, opinions[index]);
-		}
-
-	}
+            if progress && tick % report_every == 0 {
+                let mean: Vec<String> = state.network.mean_opinion().iter()
+                    .map(|value| format!("{:.3}", value))
+                    .collect();
+                let _ = writeln!(&mut io::stderr(),
+                                 "tick {}/{}: mean opinion {}, variance {:.3}",
+                                 tick, config.ticks, mean.join("/"),
+                                 bubbles::polarization(state.opinions));
+            }
+        }
+
+        let header = header && tick == first_tick;
+        if let Some(ref mut file) = network_file {
+            file.append(|out| write_network(out, state, header));
+        }
+        if let Some(ref mut file) = polarization_file {
+            file.append(|out| write_polarization(out, &config, state, header));
+        }
+        if let Some(ref mut file) = summary_file {
+            file.append(|out| write_summary(out, &config, state, header));
+        }
+
+        // --opinion-sample-interval keeps the starting state, as the network
+        // output has, then every tick of the run, or only every <n>th tick and
+        // the final tick.
+        if tick != first_tick && !state.last &&
+                interval.is_some_and(|interval| tick % interval != 0) {
+            return;
+        }
+        if let Some(ref mut file) = opinion_file {
+            file.append(|out| write_opinions(out, &config, state, args.flag_opinion_format,
+                                             header));
+        }
+        if let Some(ref mut file) = entropy_file {
+            file.append(|out| write_entropy(out, &config, state, args.flag_entropy_bins,
+                                            header));
+        }
+        if let Some(ref mut file) = heatmap_file {
+            file.append(|out| write_heatmap(out, &config, state, args.flag_heatmap_bins,
+                                            header));
+        }
+        if let Some(ref mut file) = field_file {
+            file.append(|out| write_neighbor_field(out, &config, state, header));
+        }
+        if let Some(ref mut file) = node_file {
+            file.append(|out| write_node_table(out, &config, state, header));
+        }
+    });
+    let streamed = vec![opinion_file, network_file, polarization_file, entropy_file,
+                        heatmap_file, summary_file, field_file, node_file];
+    for file in streamed.into_iter().flat_map(|file| file) {
+        file.finish();
+    }
+
+    if let Some(file) = metadata_file {
+        file.write(|out| write_metadata(out, &config, &result, args.flag_cluster_threshold,
+                                        header));
+    }
+    if let Some(file) = final_file {
+        file.write(|out| write_final_opinions(out, &config, &result, header));
+    }
+    if let Some(file) = json_file {
+        file.write(|out| bubbles::write_json(out, &config, &result));
+    }
+    if let Some(file) = graphml_file {
+        file.write(|out| bubbles::write_graphml(out, &result.network, &result.opinions));
+    }
+    if let Some(file) = matrix_file {
+        let initial;
+        let network = match args.flag_matrix_state {
+            MatrixState::Initial => {
+                initial = initial_network(&result);
+                &initial
+            },
+            MatrixState::Final => &result.network
+        };
+        file.write(|out| bubbles::write_matrix(out, network, args.flag_matrix_format));
+    }
+    if let Some(file) = gexf_file {
+        file.write(|out| bubbles::write_gexf(out, &result));
+    }
+    if let Some(file) = histogram_file {
+        file.write(|out| write_degree_histogram(out, &result, header));
+    }
+    if let Some(file) = path_file {
+        file.write(|out| write_path_report(out, &result, header));
+    }
+    if let Some(file) = events_file {
+        file.write(|out| write_events(out, &result, header));
+    }
 }
@@ -0,0 +1,111 @@
+use std;
+use std::io;
+use std::io::Write;
+
+use adjacency::Adjacency;
+
+/// Write one row of weights on a line of its own, each value followed by a
+/// space.
+pub fn write_row<W, T, I>(out: &mut W, row: I) -> io::Result<()>
+    where W: Write, T: std::fmt::Display, I: IntoIterator<Item = T> {
+    for value in row {
+        write!(out, "{} ", value)?;
+    }
+    writeln!(out)
+}
+
+/// A dense, square adjacency structure for `size` members. Relationships are
+/// symmetric: `put` sets both sides at once, so there is no sense in which
+/// the matrix could have a different number of rows and columns.
+#[derive(Debug, Clone)]
+pub struct Matrix<T> {
+    size: usize,
+    data: Vec<T>
+}
+
+impl<T> Matrix<T> where T: Default + Copy + std::fmt::Display + std::cmp::PartialEq {
+    pub fn new(size: usize) -> Self {
+        Matrix {
+            size,
+            data: vec![T::default(); size * size],
+        }
+    }
+
+    /// Write the matrix one row per line, each value followed by a space.
+    pub fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        for row in 0..self.size() {
+            write_row(out, (0..self.size()).map(|col| self.get(row, col)))?;
+        }
+        Ok(())
+    }
+
+    pub fn print(&self) {
+        let stdout = io::stdout();
+        self.write_to(&mut stdout.lock()).expect("unable to write to stdout");
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    // A column past the edge would silently land in the next row, so refuse
+    // anything outside the square.
+    fn index_for(&self, row: usize, col: usize) -> usize {
+        assert!(row < self.size && col < self.size,
+                "({}, {}) is outside a {}x{} matrix", row, col, self.size, self.size);
+        row * self.size + col
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> T {
+        let index = self.index_for(row, col);
+        self.data[index]
+    }
+
+    pub fn put(&mut self, row: usize, col: usize, value: T) {
+        self.replace(row, col, value);
+    }
+
+    /// Store `value` in both cells of the pair and return what was there,
+    /// checking bounds and working out the indices only once.
+    pub fn replace(&mut self, row: usize, col: usize, value: T) -> T {
+        let index = self.index_for(row, col);
+        let mirror = col * self.size + row;
+        let old = std::mem::replace(&mut self.data[index], value);
+        self.data[mirror] = value;
+        old
+    }
+
+    /// The members `node` has a relationship with and their weights, in
+    /// ascending order, read straight from its row.
+    pub fn neighbors<'a>(&'a self, node: usize) -> impl Iterator<Item = (usize, T)> + 'a {
+        let start = self.index_for(node, 0);
+        self.data[start..start + self.size].iter().cloned().enumerate()
+            .filter(|&(_, value)| value != T::default())
+    }
+}
+
+impl<T> Adjacency<T> for Matrix<T> where T: Default + Copy + std::fmt::Display + std::cmp::PartialEq {
+    fn new(size: usize) -> Self {
+        Matrix::new(size)
+    }
+
+    fn size(&self) -> usize {
+        Matrix::size(self)
+    }
+
+    fn get(&self, row: usize, col: usize) -> T {
+        Matrix::get(self, row, col)
+    }
+
+    fn put(&mut self, row: usize, col: usize, value: T) {
+        Matrix::put(self, row, col, value)
+    }
+
+    fn replace(&mut self, row: usize, col: usize, value: T) -> T {
+        Matrix::replace(self, row, col, value)
+    }
+
+    fn neighbors(&self, node: usize) -> Vec<(usize, T)> {
+        Matrix::neighbors(self, node).collect()
+    }
+}
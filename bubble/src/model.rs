@@ -0,0 +1,267 @@
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader};
+
+use network::Network;
+
+/// A position in opinion space, one coordinate per dimension. Each coordinate
+/// lives in the configured opinion range, `[0, 100]` by default.
+pub type Opinion = Vec<f64>;
+
+/// Load the opinions of `n` members with `dimensions` coordinates each from a
+/// CSV of `node,opinion[,opinion...]` rows, every member appearing exactly
+/// once and every coordinate lying in [min, max]. Blank lines and a header
+/// row are skipped.
+pub fn read_opinions(path: &str, n: usize, dimensions: usize, min: f64,
+                     max: f64) -> io::Result<Vec<Opinion>> {
+    let invalid = |line: usize, message: String| {
+        io::Error::new(io::ErrorKind::InvalidData,
+                       format!("{}:{}: {}", path, line, message))
+    };
+
+    let mut opinions: Vec<Option<Opinion>> = vec![None; n];
+    let reader = BufReader::new(File::open(path)?);
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        let number = index + 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(',').map(|field| field.trim()).collect();
+        if fields.len() != dimensions + 1 {
+            return Err(invalid(number, format!("expected a node and {} opinion values, \
+                                                found {:?}", dimensions, line)));
+        }
+
+        let node = match fields[0].parse::<usize>() {
+            Ok(node) => node,
+            _ if number == 1 => continue,
+            _ => return Err(invalid(number, format!("members must be indices, found {:?}",
+                                                    line)))
+        };
+        if node >= n {
+            return Err(invalid(number, format!("member {} is outside [0, {})", node, n)));
+        }
+        if opinions[node].is_some() {
+            return Err(invalid(number, format!("member {} appears more than once", node)));
+        }
+
+        let mut opinion = Vec::with_capacity(dimensions);
+        for field in &fields[1..] {
+            match field.parse::<f64>() {
+                Ok(value) if value >= min && value <= max => opinion.push(value),
+                _ => return Err(invalid(number, format!("opinion {:?} is not a number in \
+                                                         [{}, {}]", field, min, max)))
+            }
+        }
+        opinions[node] = Some(opinion);
+    }
+
+    match opinions.iter().position(|opinion| opinion.is_none()) {
+        Some(missing) => Err(io::Error::new(io::ErrorKind::InvalidData,
+                                            format!("{}: member {} has no opinion", path,
+                                                    missing))),
+        None => Ok(opinions.into_iter().map(|opinion| opinion.unwrap()).collect())
+    }
+}
+
+/// The rule used to update opinions when a message is received.
+#[derive(Debug, Clone, Copy, PartialEq, RustcDecodable)]
+pub enum Model {
+    /// Consensus strengthens ties and assimilates, opposition weakens ties
+    /// and repels.
+    Threshold,
+    /// Deffuant-Weisbuch bounded confidence.
+    Deffuant,
+    /// Hegselmann-Krause bounded confidence, updated synchronously.
+    Hk
+}
+
+/// The largest opinion difference at which two members still interact. Used
+/// by both bounded confidence models.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConfidenceBound(pub f64);
+
+/// The fraction of the opinion difference members close per interaction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConvergenceRate(pub f64);
+
+/// The state of a relationship after a message has been received.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    pub sender: Opinion,
+    pub recipient: Opinion,
+    pub strength: f64
+}
+
+pub trait OpinionModel {
+    /// React to `message` sent from a member holding opinion `sender` to one
+    /// holding opinion `recipient` over a tie of the given `strength`.
+    fn interact(&self, message: &[f64], sender: &[f64], recipient: &[f64],
+                strength: f64) -> Outcome;
+}
+
+/// The Euclidean distance between two opinions. For a single dimension this
+/// is the absolute difference.
+pub fn distance(a: &[f64], b: &[f64]) -> f64 {
+    if a.len() == 1 {
+        return (a[0] - b[0]).abs();
+    }
+    a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum::<f64>().sqrt()
+}
+
+// A full-strength tie closes one part in this many of the gap between the
+// recipient and the message. The gap is already in opinion units, so this
+// does not depend on the opinion range.
+const STEPS_TO_MESSAGE: f64 = 100.0;
+
+/// The original model, shifting both opinions and relationships depending on
+/// how far the message is from the recipient's opinion.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ThresholdModel {
+    pub consensus: f64,
+    pub opposition: f64,
+    /// The share of the usual pull towards the message still applied in the
+    /// neutral band between consensus and opposition.
+    pub neutral_drift: f64,
+    /// Scales every opinion change independently of relationship strength.
+    pub assimilation_rate: f64,
+    /// Push recipients away from messages beyond the opposition threshold.
+    /// Without it those messages only weaken the relationship.
+    pub backfire: bool,
+    /// Let relationships fall below zero, down to -1. A negative tie is an
+    /// antagonistic one: its pull is reversed, so agreeing with an enemy's
+    /// message still pushes the recipient away from it.
+    pub signed: bool,
+    /// The width of the opinion scale. Relationship changes are measured as
+    /// fractions of it, so rescaling the opinions together with the
+    /// thresholds leaves a run unchanged.
+    pub range: f64
+}
+
+impl OpinionModel for ThresholdModel {
+    fn interact(&self, message: &[f64], sender: &[f64], recipient: &[f64],
+                strength: f64) -> Outcome {
+        let mut outcome = Outcome {
+            sender: sender.to_vec(),
+            recipient: recipient.to_vec(),
+            strength
+        };
+
+        // Adjust opinions
+        let opinion_change: Vec<f64> = message.iter().zip(recipient)
+            .map(|(m, r)| self.assimilation_rate * strength * ((m - r) / STEPS_TO_MESSAGE))
+            .collect();
+
+        // Adjust social standing due to message. We're going to split this
+        // into three categories.
+        //  1. Consensus: Within 25% of each other. Increase relationship.
+        //  2. Challenged: Within 75% of each other. Leave the relationship
+        //     alone and drift towards the message by neutral_drift.
+        //  3. Irreconsilable: More that 75% different. Decrease relationship
+        let difference = distance(message, recipient);
+        if difference < self.consensus {
+            let mut new_strength = strength + (self.consensus - difference)/self.range;
+            if new_strength > 1.0 {
+                new_strength = 1.0
+            }
+            outcome.strength = new_strength;
+
+            // Adjust opinion so that the person's opinion is more in line
+            // with the message.
+            for (opinion, change) in outcome.recipient.iter_mut().zip(&opinion_change) {
+                *opinion += *change;
+            }
+
+        } else if difference > self.opposition {
+            let floor = if self.signed { -1.0 } else { 0.0 };
+            let mut new_strength = strength - (difference - self.opposition)/self.range;
+            if new_strength < floor {
+                new_strength = floor
+            }
+            outcome.strength = new_strength;
+
+            // Adjust opinion so that the person's opinion moves away from
+            // the message. A message this far off repels whoever sent it, so
+            // an enemy's pushes the recipient away just as a friend's does.
+            if self.backfire {
+                let away = if strength < 0.0 { -1.0 } else { 1.0 };
+                for (opinion, change) in outcome.recipient.iter_mut().zip(&opinion_change) {
+                    *opinion -= away * *change;
+                }
+            }
+
+        } else if self.neutral_drift != 0.0 {
+            // Mild disagreement still nudges the person towards the message.
+            for (opinion, change) in outcome.recipient.iter_mut().zip(&opinion_change) {
+                *opinion += self.neutral_drift * *change;
+            }
+        }
+
+        outcome
+    }
+}
+
+/// Deffuant-Weisbuch bounded confidence: when the message lies within the
+/// confidence bound of the recipient, both members move towards each other
+/// by the convergence rate, or away from each other over a negative tie.
+/// Otherwise nothing happens and relationships are left untouched.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeffuantModel {
+    pub bound: ConfidenceBound,
+    pub rate: ConvergenceRate
+}
+
+impl OpinionModel for DeffuantModel {
+    fn interact(&self, message: &[f64], sender: &[f64], recipient: &[f64],
+                strength: f64) -> Outcome {
+        let ConfidenceBound(bound) = self.bound;
+        let ConvergenceRate(rate) = self.rate;
+        let rate = if strength < 0.0 { -rate } else { rate };
+
+        if distance(message, recipient) >= bound {
+            return Outcome {
+                sender: sender.to_vec(),
+                recipient: recipient.to_vec(),
+                strength
+            };
+        }
+
+        Outcome {
+            sender: sender.iter().zip(recipient)
+                .map(|(s, r)| s + rate * (r - s))
+                .collect(),
+            recipient: recipient.iter().zip(message)
+                .map(|(r, m)| r + rate * (m - r))
+                .collect(),
+            strength
+        }
+    }
+}
+
+/// One synchronous Hegselmann-Krause step: every member adopts the mean of its
+/// own opinion and those of its neighbours within the confidence bound.
+///
+/// All new opinions are computed from `opinions` before any is committed, so
+/// the result does not depend on the order members are visited in.
+pub fn hegselmann_krause(network: &Network, opinions: &[Opinion],
+                         bound: ConfidenceBound) -> Vec<Opinion> {
+    let ConfidenceBound(bound) = bound;
+
+    (0..opinions.len())
+        .map(|node| {
+            let own = &opinions[node];
+            let mut total = own.clone();
+            let mut count = 1;
+            for (neighbor, _) in network.incoming(node) {
+                if distance(&opinions[neighbor], own) < bound {
+                    for (sum, value) in total.iter_mut().zip(&opinions[neighbor]) {
+                        *sum += *value;
+                    }
+                    count += 1;
+                }
+            }
+            total.iter().map(|sum| sum / count as f64).collect()
+        })
+        .collect()
+}
@@ -0,0 +1,317 @@
+use std::collections::BTreeMap;
+
+use rayon::ThreadPool;
+use rayon::prelude::*;
+
+use adjacency::Adjacency;
+use directed::DirectedMatrix;
+use matrix::Matrix;
+use model::{Opinion, OpinionModel, Outcome};
+use simulation::Boundary;
+use sparse::SparseMatrix;
+use stats;
+
+/// The generator used to build the initial network.
+#[derive(Debug, Clone, Copy, PartialEq, RustcDecodable)]
+pub enum Topology {
+    /// Watts-Strogatz small world.
+    Ws,
+    /// The regular ring lattice Watts-Strogatz starts from, never rewired.
+    Ring,
+    /// Barabási-Albert preferential attachment.
+    Ba,
+    /// Erdős-Rényi random graph.
+    Er,
+    /// Everyone connected to everyone else.
+    Complete,
+    /// Random geometric graph in the unit square.
+    Geometric,
+    /// Stochastic block model: dense blocks joined by sparser ties.
+    Sbm
+}
+
+/// A relationship between two members of the network. In an undirected
+/// network it covers both directions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Edge {
+    pub sender: usize,
+    pub recipient: usize,
+    pub weight: f64
+}
+
+/// Structural properties of a network.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetworkStats {
+    pub edge_count: usize,
+    pub density: f64,
+    /// The number of groups of members that can reach each other.
+    pub components: usize,
+    /// The number of members in the biggest such group.
+    pub largest_component: usize,
+    pub clustering_coefficient: f64,
+    pub average_path_length: f64,
+    /// `(degree, count)` for every degree held by at least one member, in
+    /// ascending order of degree.
+    pub degree_histogram: Vec<(usize, usize)>
+}
+
+#[derive(Debug, Clone)]
+enum Storage {
+    Dense(Matrix<f64>),
+    Sparse(SparseMatrix<f64>),
+    Directed(DirectedMatrix<f64>)
+}
+
+/// A weighted social network and the opinions of its members. Relationship
+/// strengths live in `[0, 1]` and a weight of zero means the two members are
+/// not connected. A network built from storage alone holds no opinions until
+/// `set_opinions` gives it some.
+///
+/// Each member's neighbours are kept in a list alongside the storage, so
+/// visiting them costs O(degree) rather than a scan of the whole row. The
+/// list changes only when a weight crosses zero.
+#[derive(Debug, Clone)]
+pub struct Network {
+    storage: Storage,
+    lists: Vec<Vec<usize>>,
+    opinions: Vec<Opinion>
+}
+
+// Deliver `message` from `sender` to `recipient`. A mutual exchange also has
+// the recipient reply with its exact opinion over the same tie, moving the
+// sender the way the recipient would have moved. The tie itself only changes
+// once, in reaction to the message.
+fn exchange(model: &dyn OpinionModel, mutual: bool, message: &[f64], sender: &[f64],
+            recipient: &[f64], strength: f64) -> Outcome {
+    let mut outcome = model.interact(message, sender, recipient, strength);
+    if mutual {
+        let reply = model.interact(recipient, recipient, sender, strength);
+        outcome.sender = outcome.sender.iter().zip(&reply.recipient).zip(sender)
+            .map(|((current, moved), before)| current + (moved - before))
+            .collect();
+    }
+    outcome
+}
+
+impl Network {
+    fn new(storage: Storage) -> Self {
+        let mut network = Network { storage, lists: Vec::new(), opinions: Vec::new() };
+        network.lists = network.adjacency().to_adjacency_list().into_iter()
+            .map(|list| list.into_iter().map(|(neighbor, _)| neighbor).collect())
+            .collect();
+        network
+    }
+
+    // Keep `from`'s list in step with a weight that just changed from `old`
+    // to `new`. Lists stay in ascending order.
+    fn relink(&mut self, from: usize, to: usize, old: f64, new: f64) {
+        let list = &mut self.lists[from];
+        match list.binary_search(&to) {
+            Err(position) if old == 0.0 && new != 0.0 => list.insert(position, to),
+            Ok(position) if old != 0.0 && new == 0.0 => { list.remove(position); },
+            _ => {}
+        }
+    }
+
+    fn adjacency(&self) -> &dyn Adjacency<f64> {
+        match self.storage {
+            Storage::Dense(ref matrix) => matrix,
+            Storage::Sparse(ref matrix) => matrix,
+            Storage::Directed(ref matrix) => matrix
+        }
+    }
+
+    fn adjacency_mut(&mut self) -> &mut dyn Adjacency<f64> {
+        match self.storage {
+            Storage::Dense(ref mut matrix) => matrix,
+            Storage::Sparse(ref mut matrix) => matrix,
+            Storage::Directed(ref mut matrix) => matrix
+        }
+    }
+
+    /// A network of the same size in the same kind of storage, with no
+    /// relationships and no opinions.
+    pub fn empty_like(&self) -> Network {
+        let size = self.size();
+        Network::new(match self.storage {
+            Storage::Dense(_) => Storage::Dense(Matrix::new(size)),
+            Storage::Sparse(_) => Storage::Sparse(SparseMatrix::new(size)),
+            Storage::Directed(_) => Storage::Directed(DirectedMatrix::new(size))
+        })
+    }
+
+    /// Whether the strength of a relationship can differ between its two
+    /// directions.
+    pub fn is_directed(&self) -> bool {
+        matches!(self.storage, Storage::Directed(_))
+    }
+
+    pub fn size(&self) -> usize {
+        self.adjacency().size()
+    }
+
+    pub fn weight(&self, sender: usize, recipient: usize) -> f64 {
+        self.adjacency().get(sender, recipient)
+    }
+
+    pub fn set_weight(&mut self, sender: usize, recipient: usize, weight: f64) {
+        let old = self.adjacency_mut().replace(sender, recipient, weight);
+        self.relink(sender, recipient, old, weight);
+        if !self.is_directed() {
+            self.relink(recipient, sender, old, weight);
+        }
+    }
+
+    pub fn opinions(&self) -> &[Opinion] {
+        &self.opinions
+    }
+
+    pub fn opinion(&self, node: usize) -> &Opinion {
+        &self.opinions[node]
+    }
+
+    /// Replace every member's opinion, in member order.
+    pub fn set_opinions(&mut self, opinions: Vec<Opinion>) {
+        assert_eq!(opinions.len(), self.size(), "every member needs an opinion");
+        self.opinions = opinions;
+    }
+
+    pub fn set_opinion(&mut self, node: usize, opinion: Opinion) {
+        self.opinions[node] = opinion;
+    }
+
+    /// Bring every opinion back into [min, max] the way `boundary` says.
+    pub fn clamp_opinions(&mut self, boundary: Boundary, min: f64, max: f64) {
+        for value in self.opinions.iter_mut().flat_map(|opinion| opinion.iter_mut()) {
+            *value = boundary.apply(*value, min, max);
+        }
+    }
+
+    /// See `stats::mean_opinion`.
+    pub fn mean_opinion(&self) -> Opinion {
+        stats::mean_opinion(&self.opinions)
+    }
+
+    /// What `message` from `sender` does to each member it reaches, as
+    /// `(recipient, strength, outcome)` in neighbour order. Every outcome is
+    /// worked out from the opinions as they stand, in parallel when given a
+    /// thread pool, and it is up to the caller to apply them. In a mutual
+    /// exchange each recipient also replies with its exact opinion, moving
+    /// the sender the way the recipient would have moved.
+    pub fn broadcast(&self, sender: usize, message: &[f64], model: &(dyn OpinionModel + Sync),
+                     mutual: bool, pool: Option<&ThreadPool>) -> Vec<(usize, f64, Outcome)> {
+        let neighbors = self.neighbors(sender);
+        let deliver = |&(recipient, strength): &(usize, f64)| {
+            let outcome = exchange(model, mutual, message, &self.opinions[sender],
+                                   &self.opinions[recipient], strength);
+            (recipient, strength, outcome)
+        };
+        match pool {
+            Some(pool) => pool.install(|| neighbors.par_iter().map(deliver).collect()),
+            None => neighbors.iter().map(deliver).collect()
+        }
+    }
+
+    /// The members connected to `node` and the strength of each relationship.
+    /// In a directed network these are the relationships `node` sends along.
+    pub fn neighbors(&self, node: usize) -> Vec<(usize, f64)> {
+        let adjacency = self.adjacency();
+        self.lists[node].iter()
+            .map(|&neighbor| (neighbor, adjacency.get(node, neighbor)))
+            .collect()
+    }
+
+    /// The members that influence `node` and the strength of each
+    /// relationship towards it.
+    pub fn incoming(&self, node: usize) -> Vec<(usize, f64)> {
+        self.adjacency().incoming(node)
+    }
+
+    /// See `Adjacency::average_weighted_path_length`.
+    pub fn average_weighted_path_length(&self) -> f64 {
+        self.adjacency().average_weighted_path_length()
+    }
+
+    /// See `Adjacency::modularity`.
+    pub fn modularity(&self, communities: &[usize]) -> f64 {
+        self.adjacency().modularity(communities)
+    }
+
+    /// See `Adjacency::is_symmetric`. Only directed networks may fail it.
+    pub fn is_symmetric(&self) -> bool {
+        self.adjacency().is_symmetric()
+    }
+
+    pub fn stats(&self) -> NetworkStats {
+        let components = self.connected_components();
+        NetworkStats {
+            edge_count: self.adjacency().edge_count(),
+            density: self.adjacency().density(),
+            components: components.len(),
+            largest_component: components.iter().map(|component| component.len())
+                .max().unwrap_or(0),
+            clustering_coefficient: self.adjacency().clustering_coefficient(),
+            average_path_length: self.adjacency().average_path_length(),
+            degree_histogram: self.degree_histogram()
+        }
+    }
+
+    /// The summed strength of the relationships `node` sends along, with a
+    /// hostile tie counting by its magnitude.
+    pub fn strength(&self, node: usize) -> f64 {
+        self.neighbors(node).iter().map(|&(_, weight)| weight.abs()).sum()
+    }
+
+    /// See `Adjacency::connected_components`.
+    pub fn connected_components(&self) -> Vec<Vec<usize>> {
+        self.adjacency().connected_components()
+    }
+
+    /// The number of members `node` is connected to, counting only outgoing
+    /// relationships in a directed network.
+    pub fn degree(&self, node: usize) -> usize {
+        self.neighbors(node).len()
+    }
+
+    pub fn degree_histogram(&self) -> Vec<(usize, usize)> {
+        let mut counts: BTreeMap<usize, usize> = BTreeMap::new();
+        for node in 0..self.size() {
+            *counts.entry(self.degree(node)).or_insert(0) += 1;
+        }
+        counts.into_iter().collect()
+    }
+
+    /// Every connected pair, listed once with `sender > recipient`. A
+    /// directed network lists each direction as its own edge instead.
+    pub fn edges(&self) -> Vec<Edge> {
+        let directed = self.is_directed();
+        let mut edges = Vec::new();
+        for sender in 0..self.size() {
+            for (recipient, weight) in self.neighbors(sender) {
+                if !directed && recipient >= sender {
+                    break
+                }
+                edges.push(Edge { sender, recipient, weight });
+            }
+        }
+        edges
+    }
+}
+
+impl From<Matrix<f64>> for Network {
+    fn from(matrix: Matrix<f64>) -> Self {
+        Network::new(Storage::Dense(matrix))
+    }
+}
+
+impl From<SparseMatrix<f64>> for Network {
+    fn from(matrix: SparseMatrix<f64>) -> Self {
+        Network::new(Storage::Sparse(matrix))
+    }
+}
+
+impl From<DirectedMatrix<f64>> for Network {
+    fn from(matrix: DirectedMatrix<f64>) -> Self {
+        Network::new(Storage::Directed(matrix))
+    }
+}
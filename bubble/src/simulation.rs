@@ -0,0 +1,1058 @@
+use std::collections::VecDeque;
+use std::f64;
+use std::io;
+use std::io::Write;
+
+use rand;
+use rand::{Rng, SeedableRng, StdRng};
+use rand::distributions::{IndependentSample, Normal};
+use rayon::{Configuration, ThreadPool};
+
+use adjacency::{unit_square_positions, Adjacency, Position};
+use checkpoint::Checkpoint;
+use directed::DirectedMatrix;
+use matrix::Matrix;
+use model::{distance, hegselmann_krause, ConfidenceBound, ConvergenceRate, DeffuantModel, Model,
+            Opinion, OpinionModel, ThresholdModel};
+use network::{Network, NetworkStats, Topology};
+use sparse::SparseMatrix;
+use stats;
+
+/// The random draws a run makes once it is under way. Every draw has a
+/// default built on `Rng`, so any generator can drive a run; a test can
+/// override them to script exactly who sends what, and still rely on the
+/// underlying generator to build the network and initial opinions.
+pub trait SimulationRng: Rng + Sized {
+    /// The member that broadcasts next.
+    fn sender(&mut self, population: usize) -> usize {
+        self.gen_range(0, population)
+    }
+
+    /// One coordinate of a message sent by a member holding `opinion`.
+    fn message(&mut self, opinion: f64, stddev: f64) -> f64 {
+        Normal::new(opinion, stddev).ind_sample(self)
+    }
+
+    /// The index of the candidate a member rewires to, out of `candidates`.
+    fn rewire_target(&mut self, candidates: usize) -> usize {
+        self.gen_range(0, candidates)
+    }
+
+    /// How many words the generator has produced, if it keeps count. Only
+    /// runs whose generator does can write checkpoints.
+    fn draws(&self) -> Option<u64> {
+        None
+    }
+}
+
+impl SimulationRng for StdRng {}
+
+// The standard generator, counting the words it hands out so that a resumed
+// run can wind a fresh one forward to the same place. Every draw from it,
+// 32 or 64 bits, consumes exactly one 64-bit word.
+struct CountedRng {
+    rng: StdRng,
+    draws: u64
+}
+
+impl CountedRng {
+    fn new(seed: u64) -> Self {
+        CountedRng { rng: SeedableRng::from_seed(&[seed as usize][..]), draws: 0 }
+    }
+
+    fn skip(&mut self, draws: u64) {
+        for _ in 0..draws {
+            self.next_u64();
+        }
+    }
+}
+
+impl Rng for CountedRng {
+    fn next_u32(&mut self) -> u32 {
+        self.draws += 1;
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.draws += 1;
+        self.rng.next_u64()
+    }
+}
+
+impl SimulationRng for CountedRng {
+    fn draws(&self) -> Option<u64> {
+        Some(self.draws)
+    }
+}
+
+/// How the opinions everyone starts with are drawn.
+#[derive(Debug, Clone, Copy, PartialEq, RustcDecodable)]
+pub enum InitialDistribution {
+    /// A single Gaussian around the initial mean.
+    Normal,
+    /// Evenly over the opinion range, ignoring the mean and spread.
+    Uniform,
+    /// Two Gaussians either side of the initial mean, for a population that
+    /// starts out split.
+    Bimodal
+}
+
+/// What happens to an opinion pushed past the edge of the opinion range.
+#[derive(Debug, Clone, Copy, PartialEq, RustcDecodable)]
+pub enum Boundary {
+    /// Pin it to the edge.
+    Clamp,
+    /// Bounce it back inside by as far as it overshot.
+    Reflect
+}
+
+impl Boundary {
+    /// Bring `value` back into [min, max].
+    pub fn apply(self, value: f64, min: f64, max: f64) -> f64 {
+        let mut value = value;
+        if self == Boundary::Reflect {
+            if value < min {
+                value = 2.0 * min - value;
+            } else if value > max {
+                value = 2.0 * max - value;
+            }
+        }
+        // Clamp, which also catches a reflection that overshot the whole
+        // range.
+        value.max(min).min(max)
+    }
+}
+
+/// How open each member is to being moved by the messages they receive.
+#[derive(Debug, Clone, Copy, PartialEq, RustcDecodable)]
+pub enum OpennessDistribution {
+    /// Everyone takes in the full change.
+    Constant,
+    /// Evenly over [0, 1], from members nothing moves to fully open ones.
+    Uniform
+}
+
+/// How the spread of messages changes over the course of a run.
+#[derive(Debug, Clone, Copy, PartialEq, RustcDecodable)]
+pub enum NoiseSchedule {
+    /// Every message uses the same spread.
+    Constant,
+    /// The spread falls in equal steps to its final value.
+    Linear,
+    /// The spread falls by the same factor every tick to its final value.
+    Exponential
+}
+
+/// An outside source, such as mass media, that pushes the same opinion at a
+/// random share of the population every tick.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Media {
+    /// The opinion broadcast on every issue.
+    pub opinion: f64,
+    /// The fraction of the population the broadcast reaches each tick.
+    pub reach: f64,
+    /// How strong a tie the audience treats the broadcast as coming over.
+    pub strength: f64
+}
+
+/// Parameters for a single simulation run.
+#[derive(Debug, Clone)]
+pub struct SimulationConfig {
+    pub population: usize,
+    pub degree: usize,
+    pub rewire: f64,
+    pub consensus: f64,
+    pub opposition: f64,
+    pub seed: u64,
+    pub topology: Topology,
+    /// How close members of a geometric network must be to connect.
+    pub radius: f64,
+    /// The strength every generated relationship starts with, and every
+    /// relationship formed by rewiring.
+    pub initial_weight: f64,
+    /// Draw each generated relationship's starting strength uniformly from
+    /// (0, 1] instead.
+    pub randomize_weights: bool,
+    /// Once opinions are drawn, set each relationship's starting strength
+    /// from how close its members' opinions are: one for identical opinions,
+    /// falling to zero for opposite corners of the opinion space.
+    pub homophilous_init: bool,
+    /// How many members each block of a stochastic block model holds, in
+    /// member order.
+    pub block_sizes: Vec<usize>,
+    /// The chance that two members of the same block are connected.
+    pub p_in: f64,
+    /// The chance that two members of different blocks are connected.
+    pub p_out: f64,
+    pub sparse: bool,
+    /// Let the two directions of a relationship evolve independently, so a
+    /// message only ever changes the sender's tie to the recipient.
+    pub directed: bool,
+    /// In a directed network, have a message that weakens a relationship
+    /// weaken the recipient's tie to the sender instead of the sender's tie
+    /// to the recipient, so conflicts leave one-sided grudges.
+    pub grudges: bool,
+    /// Let relationship strengths range over [-1, 1] instead of [0, 1]. A
+    /// negative tie is antagonistic and pushes its recipient away.
+    pub signed: bool,
+    /// Have every recipient answer with its own opinion, so the sender is
+    /// persuaded or repelled as well. Without this an undirected tie is
+    /// shared by both members but only the recipient's opinion moves.
+    pub mutual: bool,
+    /// Keep every tick's opinions and relationship changes in the result.
+    /// The opinions alone take `ticks * population * dimensions` floats, so
+    /// leave this off for large runs that only need the final state.
+    pub record_history: bool,
+    pub full_network_dump: bool,
+    pub model: Model,
+    pub confidence_bound: ConfidenceBound,
+    pub convergence_rate: ConvergenceRate,
+    pub dimensions: usize,
+    /// The lowest opinion anyone can hold.
+    pub opinion_min: f64,
+    /// The highest opinion anyone can hold.
+    pub opinion_max: f64,
+    pub boundary: Boundary,
+    pub zealots: f64,
+    /// Broadcast to part of the population every tick of an asynchronous
+    /// model.
+    pub media: Option<Media>,
+    /// How far threshold members drift towards messages in the neutral band.
+    pub neutral_drift: f64,
+    /// How quickly threshold members are persuaded, whatever their ties.
+    pub assimilation_rate: f64,
+    /// How much of their previous opinion members hold on to after every
+    /// change, from 0 (none) to 1 (never moving).
+    pub inertia: f64,
+    /// Whether threshold members are pushed away from messages they oppose.
+    pub backfire: bool,
+    pub init_distribution: InitialDistribution,
+    pub openness_distribution: OpennessDistribution,
+    /// Centre of the opinions everyone starts with.
+    pub init_mean: f64,
+    /// Spread of the opinions everyone starts with.
+    pub init_stddev: f64,
+    /// Distance between the two peaks of a bimodal start.
+    pub init_separation: f64,
+    /// Spread of each message around its sender's opinion.
+    pub message_stddev: f64,
+    /// How the spread moves from `message_stddev` on the first tick to
+    /// `final_message_stddev` on the last.
+    pub noise_schedule: NoiseSchedule,
+    pub final_message_stddev: f64,
+    /// Simulate on this network instead of generating one, in whatever
+    /// storage it already has. Its size must match `population`.
+    pub network: Option<Network>,
+    /// Start from these opinions instead of drawing them, one per member.
+    pub opinions: Option<Vec<Opinion>>,
+    pub ticks: usize,
+    /// How many randomly picked senders broadcast during each tick of an
+    /// asynchronous model.
+    pub messages_per_tick: usize,
+    /// Amplify the influence of senders with more, stronger relationships.
+    pub reach_weighting: bool,
+    /// When a relationship is removed, let the recipient replace it with one
+    /// to a like-minded member.
+    pub rewire_on_conflict: bool,
+    /// Relationships weaker than this are dormant: messages along them still
+    /// change their strength, so they can recover, but move no opinions.
+    pub min_active_weight: f64,
+    /// Work out each broadcast's outcomes on this many threads. Every outcome
+    /// is then computed from the opinions at the start of the broadcast, so
+    /// the result does not depend on the number of threads.
+    pub threads: Option<usize>,
+    pub convergence_epsilon: Option<f64>,
+    pub stable_window: usize,
+    /// Ticks before this one are transient: convergence and oscillation are
+    /// only judged on the opinion changes after it.
+    pub burn_in: usize,
+    /// Save the state of the run to `checkpoint_path` every this many ticks.
+    pub checkpoint_interval: Option<usize>,
+    pub checkpoint_path: String,
+    /// Carry on from a checkpoint instead of starting afresh. Every other
+    /// parameter should match the run that wrote it.
+    pub resume: Option<Checkpoint>
+}
+
+impl SimulationConfig {
+    pub fn new(population: usize, degree: usize, rewire: f64, consensus: f64,
+               opposition: f64, seed: u64) -> Self {
+        SimulationConfig {
+            population,
+            degree,
+            rewire,
+            consensus,
+            opposition,
+            seed,
+            topology: Topology::Ws,
+            radius: 0.1,
+            initial_weight: 0.5,
+            randomize_weights: false,
+            homophilous_init: false,
+            block_sizes: Vec::new(),
+            p_in: 0.5,
+            p_out: 0.05,
+            sparse: false,
+            directed: false,
+            grudges: false,
+            signed: false,
+            mutual: false,
+            record_history: false,
+            full_network_dump: false,
+            model: Model::Threshold,
+            confidence_bound: ConfidenceBound(20.0),
+            convergence_rate: ConvergenceRate(0.3),
+            dimensions: 1,
+            opinion_min: 0.0,
+            opinion_max: 100.0,
+            boundary: Boundary::Clamp,
+            zealots: 0.0,
+            media: None,
+            neutral_drift: 0.0,
+            assimilation_rate: 1.0,
+            inertia: 0.0,
+            backfire: true,
+            init_distribution: InitialDistribution::Normal,
+            openness_distribution: OpennessDistribution::Constant,
+            init_mean: 50.0,
+            init_stddev: 10.0,
+            init_separation: 50.0,
+            message_stddev: 10.0,
+            noise_schedule: NoiseSchedule::Constant,
+            final_message_stddev: 1.0,
+            network: None,
+            opinions: None,
+            ticks: 10000,
+            messages_per_tick: 1,
+            reach_weighting: false,
+            rewire_on_conflict: false,
+            min_active_weight: 0.0,
+            threads: None,
+            convergence_epsilon: None,
+            stable_window: 100,
+            burn_in: 0,
+            checkpoint_interval: None,
+            checkpoint_path: "checkpoint.txt".to_string(),
+            resume: None
+        }
+    }
+
+    /// The spread of messages sent during `tick`, counting from 1.
+    pub fn message_stddev_at(&self, tick: usize) -> f64 {
+        let progress = if self.ticks > 1 {
+            (tick - 1) as f64 / (self.ticks - 1) as f64
+        } else {
+            0.0
+        };
+        let (initial, last) = (self.message_stddev, self.final_message_stddev);
+        match self.noise_schedule {
+            NoiseSchedule::Constant => initial,
+            NoiseSchedule::Linear => initial + (last - initial) * progress,
+            NoiseSchedule::Exponential => initial * (last / initial).powf(progress)
+        }
+    }
+}
+
+/// The weight of a relationship as observed at a given tick.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EdgeRecord {
+    pub tick: usize,
+    pub sender: usize,
+    pub recipient: usize,
+    pub weight: f64
+}
+
+/// How the topology of the network changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeEventKind {
+    /// A relationship's weight rose from zero.
+    Formed,
+    /// A relationship's weight fell to zero.
+    Removed
+}
+
+/// A relationship appearing or disappearing at a given tick.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EdgeEvent {
+    pub tick: usize,
+    pub kind: EdgeEventKind,
+    pub sender: usize,
+    pub recipient: usize
+}
+
+/// The state of the whole population at the end of a tick, taken over every
+/// member's opinion in every dimension.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TickSummary {
+    pub tick: usize,
+    pub mean: f64,
+    pub variance: f64,
+    pub min: f64,
+    pub max: f64,
+    /// How many relationships had a non-zero weight.
+    pub edges: usize
+}
+
+// Summarise every member's opinion in every dimension at the end of `tick`.
+fn summarize(tick: usize, opinions: &[Opinion], edges: usize) -> TickSummary {
+    let values: Vec<f64> = opinions.iter().flat_map(|opinion| opinion.iter().cloned()).collect();
+    TickSummary {
+        tick,
+        mean: stats::mean(&values),
+        variance: stats::variance(&values),
+        min: values.iter().cloned().fold(f64::INFINITY, f64::min),
+        max: values.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+        edges
+    }
+}
+
+/// Where a run stands at the end of a tick, handed to the observer of
+/// `simulate_observed`. The observer first sees the state before the first
+/// tick, with every relationship in `records`.
+pub struct TickState<'a> {
+    pub tick: usize,
+    pub opinions: &'a [Opinion],
+    pub network: &'a Network,
+    /// The relationships that changed during the tick, or every relationship
+    /// the messages touched when `full_network_dump` was set.
+    pub records: &'a [EdgeRecord],
+    pub summary: &'a TickSummary,
+    /// Whether this is the last tick of the run, because it converged or
+    /// reached `ticks`.
+    pub last: bool
+}
+
+/// Everything a run produced. `opinion_history[t]` holds the opinions at the
+/// end of tick `t + 1`. `edge_history` starts with every edge at tick 0 and
+/// afterwards only holds the relationships that changed, unless
+/// `full_network_dump` was set. Without `record_history` the opinion history
+/// is empty and the edge history holds only the starting network.
+#[derive(Debug, Clone)]
+pub struct SimulationResult {
+    pub network: Network,
+    /// The tick the run started from: 0, or the checkpoint's tick when it
+    /// was resumed. Histories start after it.
+    pub first_tick: usize,
+    /// The structure of the network before the first tick.
+    pub initial_stats: NetworkStats,
+    /// Where each member was placed, for spatial topologies.
+    pub positions: Option<Vec<Position>>,
+    /// How strongly each member resists opinion changes. Zealots hold 1.0.
+    pub stubbornness: Vec<f64>,
+    /// The share of each change a member takes in from what it receives.
+    pub openness: Vec<f64>,
+    /// The opinions everyone started with, before the first tick.
+    pub initial_opinions: Vec<Opinion>,
+    pub opinions: Vec<Opinion>,
+    pub opinion_history: Vec<Vec<Opinion>>,
+    pub edge_history: Vec<EdgeRecord>,
+    /// Every relationship that formed or was removed, in order.
+    pub events: Vec<EdgeEvent>,
+    /// The population at the first tick and at the end of every tick after.
+    pub summary: Vec<TickSummary>,
+    /// The tick the run stopped at after converging, if it did.
+    pub converged_at: Option<usize>,
+    /// Whether a run that did not converge was still moving as much at the
+    /// end as over the window before, rather than settling down slowly.
+    pub oscillating: bool
+}
+
+// Every generator but the complete graph and the block model is
+// parameterised so that its mean degree matches `degree`: preferential
+// attachment adds degree/2 edges per member and the random graph connects
+// pairs with probability degree/(n-1).
+//
+// Spatial networks are built from `positions`, which the caller draws so that
+// it can keep them.
+fn generate<A, R>(config: &SimulationConfig, positions: &Option<Vec<Position>>,
+                  rng: &mut R) -> A
+        where A: Adjacency<f64>, R: Rng {
+    let n = config.population;
+    let weight = config.initial_weight;
+    let mut network = match (positions, config.topology) {
+        (&Some(ref positions), _) => A::geometric(positions, config.radius, weight),
+        (_, Topology::Ws) => A::wattz_strogatz(n, config.degree, config.rewire, weight, rng),
+        (_, Topology::Ring) => A::ring_lattice(n, config.degree, weight),
+        (_, Topology::Ba) => A::barabasi_albert(n, config.degree / 2, weight, rng),
+        (_, Topology::Er) => A::erdos_renyi(n, config.degree as f64 / (n as f64 - 1.0),
+                                            weight, rng),
+        (_, Topology::Complete) => A::complete(n, weight),
+        (_, Topology::Sbm) => A::stochastic_block_model(config.block_sizes.clone(), config.p_in,
+                                                        config.p_out, weight, rng),
+        (_, Topology::Geometric) => unreachable!("geometric networks are built from positions")
+    };
+
+    // Redraw every relationship's strength from (0, 1], so that none starts
+    // out already broken.
+    if config.randomize_weights {
+        for row in 0..n {
+            for (col, _) in network.neighbors(row) {
+                if col < row {
+                    network.put(row, col, 1.0 - rng.next_f64());
+                }
+            }
+        }
+    }
+    network
+}
+
+// The per-message rule for asynchronous models, or `None` when the model
+// updates every member synchronously.
+fn asynchronous_model(config: &SimulationConfig) -> Option<Box<dyn OpinionModel + Sync>> {
+    match config.model {
+        Model::Threshold => Some(Box::new(ThresholdModel {
+            consensus: config.consensus,
+            opposition: config.opposition,
+            neutral_drift: config.neutral_drift,
+            assimilation_rate: config.assimilation_rate,
+            backfire: config.backfire,
+            signed: config.signed,
+            range: config.opinion_max - config.opinion_min
+        })),
+        Model::Deffuant => Some(Box::new(DeffuantModel {
+            bound: config.confidence_bound,
+            rate: config.convergence_rate
+        })),
+        Model::Hk => None
+    }
+}
+
+// Rejection sampling gives up after this many draws outside the range and
+// falls back to the clamped mean, so a degenerate distribution (no spread, a
+// peak far outside the domain) cannot stall the run.
+const MAX_REJECTIONS: usize = 1000;
+
+// Sample from `distribution` truncated to [min, max] by redrawing anything
+// that falls outside, which leaves the shape inside the domain untouched.
+fn truncated_sample<R: Rng>(distribution: &Normal, mean: f64, min: f64, max: f64,
+                            rng: &mut R) -> f64 {
+    for _ in 0..MAX_REJECTIONS {
+        let sample = distribution.ind_sample(rng);
+        if sample >= min && sample <= max {
+            return sample;
+        }
+    }
+    mean.max(min).min(max)
+}
+
+// Draw one member's starting opinion. A bimodal member picks a side once and
+// holds it across every dimension.
+fn initial_opinion<R: Rng>(config: &SimulationConfig, rng: &mut R) -> Opinion {
+    let mean = match config.init_distribution {
+        InitialDistribution::Bimodal if rng.gen::<bool>() => {
+            config.init_mean - config.init_separation / 2.0
+        },
+        InitialDistribution::Bimodal => config.init_mean + config.init_separation / 2.0,
+        _ => config.init_mean
+    };
+    let distribution = Normal::new(mean, config.init_stddev);
+    (0..config.dimensions)
+        .map(|_| match config.init_distribution {
+            InitialDistribution::Uniform => {
+                rng.gen_range(config.opinion_min, config.opinion_max)
+            },
+            _ => truncated_sample(&distribution, mean, config.opinion_min,
+                                  config.opinion_max, rng)
+        })
+        .collect()
+}
+
+// Draw the positions a spatial network needs and build the network in the
+// storage the config asks for. A directed network starts out symmetric and
+// only drifts apart as messages travel along one direction at a time.
+fn build<R: Rng>(config: &SimulationConfig, rng: &mut R) -> (Network, Option<Vec<Position>>) {
+    let positions = if config.topology == Topology::Geometric && config.network.is_none() {
+        Some(unit_square_positions(config.population, &mut *rng))
+    } else {
+        None
+    };
+    let network = if let Some(ref network) = config.network {
+        network.clone()
+    } else if config.directed {
+        DirectedMatrix::from(generate::<Matrix<f64>, _>(config, &positions, &mut *rng)).into()
+    } else if config.sparse {
+        generate::<SparseMatrix<f64>, _>(config, &positions, &mut *rng).into()
+    } else {
+        generate::<Matrix<f64>, _>(config, &positions, &mut *rng).into()
+    };
+    (network, positions)
+}
+
+/// Build the network a run with this config starts from, without simulating
+/// anything.
+pub fn build_network(config: &SimulationConfig) -> Network {
+    let mut rng: StdRng = SeedableRng::from_seed(&[config.seed as usize][..]);
+    build(config, &mut rng).0
+}
+
+// How often debug builds check that an undirected network is still symmetric.
+const SYMMETRY_CHECK_INTERVAL: usize = 100;
+
+// Rebuild the network saved in a checkpoint.
+fn restore<A: Adjacency<f64>>(checkpoint: &Checkpoint) -> A {
+    let mut matrix = A::new(checkpoint.opinions.len());
+    for edge in &checkpoint.edges {
+        matrix.put(edge.sender, edge.recipient, edge.weight);
+    }
+    matrix
+}
+
+fn snapshot(network: &Network, tick: usize) -> Vec<EdgeRecord> {
+    network.edges().iter()
+        .map(|edge| EdgeRecord {
+            tick,
+            sender: edge.sender,
+            recipient: edge.recipient,
+            weight: edge.weight
+        })
+        .collect()
+}
+
+// The topology change, if any, when a weight moves from `old` to `new`.
+fn edge_event(tick: usize, sender: usize, recipient: usize, old: f64,
+              new: f64) -> Option<EdgeEvent> {
+    let kind = if old == 0.0 && new != 0.0 {
+        EdgeEventKind::Formed
+    } else if old != 0.0 && new == 0.0 {
+        EdgeEventKind::Removed
+    } else {
+        return None;
+    };
+    Some(EdgeEvent { tick, kind, sender, recipient })
+}
+
+// Pick someone for `agent` to befriend after falling out with `former`: any
+// member it is not already connected to whose opinion lies within `consensus`
+// of its own.
+fn like_minded<R: SimulationRng>(network: &Network, agent: usize, former: usize,
+                                 consensus: f64, rng: &mut R) -> Option<usize> {
+    let candidates: Vec<usize> = (0..network.size())
+        .filter(|&other| other != agent && other != former)
+        .filter(|&other| network.weight(agent, other) == 0.0)
+        .filter(|&other| distance(network.opinion(agent), network.opinion(other)) < consensus)
+        .collect();
+    if candidates.is_empty() {
+        return None;
+    }
+    Some(candidates[rng.rewire_target(candidates.len())])
+}
+
+// Whether the mean opinion change per tick over a full window failed to
+// decay: the later half of the window moved at least as much as the earlier
+// half, and something still moved.
+fn oscillating(changes: &VecDeque<f64>) -> bool {
+    let half = changes.len() / 2;
+    if half == 0 {
+        return false;
+    }
+    let earlier = changes.iter().take(half).sum::<f64>() / half as f64;
+    let later = changes.iter().skip(half).sum::<f64>() / (changes.len() - half) as f64;
+    later > 0.0 && later >= earlier
+}
+
+// Scale the change from `old` to `new` by `factor`.
+fn scale_change(old: &[f64], new: Opinion, factor: f64) -> Opinion {
+    if factor == 1.0 {
+        return new;
+    }
+    old.iter().zip(new)
+        .map(|(before, after)| before + factor * (after - before))
+        .collect()
+}
+
+// Apply only the share of a change that a member with the given stubbornness
+// lets through, and of that only the share its inertia does not hold back:
+// the result blends `inertia * old + (1 - inertia) * new`.
+fn resist(old: &[f64], new: Opinion, stubbornness: f64, inertia: f64) -> Opinion {
+    scale_change(old, new, (1.0 - stubbornness) * (1.0 - inertia))
+}
+
+// Each member's social reach: the summed strength of its relationships
+// divided by the population's mean, so a typical member has a reach of 1.0,
+// a hub more and a leaf less. Everyone has a reach of 1.0 in a network
+// without any relationships.
+//
+//     reach(i) = strength(i) / (sum_j strength(j) / n)
+fn reach(network: &Network) -> Vec<f64> {
+    let strengths: Vec<f64> = (0..network.size()).map(|node| network.strength(node)).collect();
+    let mean = strengths.iter().sum::<f64>() / strengths.len() as f64;
+    if mean == 0.0 {
+        return vec![1.0; strengths.len()];
+    }
+    strengths.iter().map(|strength| strength / mean).collect()
+}
+
+pub fn simulate(config: &SimulationConfig) -> SimulationResult {
+    simulate_observed(config, |_| {})
+}
+
+/// Run a simulation, handing the state of the run to `observe` before the
+/// first tick and at the end of every tick, so that per-tick outputs can be
+/// written without keeping the history.
+pub fn simulate_observed<F>(config: &SimulationConfig, observe: F) -> SimulationResult
+        where F: FnMut(&TickState) {
+    let mut rng = CountedRng::new(config.seed);
+    if let Some(ref checkpoint) = config.resume {
+        rng.skip(checkpoint.draws);
+    }
+    simulate_with(config, &mut rng, observe)
+}
+
+/// Run a simulation drawing every random number from `rng` instead of a
+/// generator seeded from `config.seed`.
+pub fn simulate_with<R, F>(config: &SimulationConfig, rng: &mut R,
+                           mut observe: F) -> SimulationResult
+        where R: SimulationRng, F: FnMut(&TickState) {
+    let population = config.population;
+    let model = asynchronous_model(config);
+    let pool = config.threads.map(|threads| {
+        ThreadPool::new(Configuration::new().num_threads(threads))
+            .expect("unable to start the thread pool")
+    });
+
+    // A resumed run takes everything the setup below would draw from its
+    // checkpoint instead.
+    let resume = config.resume.as_ref();
+    let first_tick = resume.map_or(0, |checkpoint| checkpoint.tick);
+
+    // Generate the network
+    let (mut social_network, positions): (Network, _) = match resume {
+        Some(checkpoint) if config.directed => {
+            (restore::<DirectedMatrix<f64>>(checkpoint).into(), checkpoint.positions.clone())
+        },
+        Some(checkpoint) if config.sparse => {
+            (restore::<SparseMatrix<f64>>(checkpoint).into(), checkpoint.positions.clone())
+        },
+        Some(checkpoint) => {
+            (restore::<Matrix<f64>>(checkpoint).into(), checkpoint.positions.clone())
+        },
+        None => build(config, &mut *rng)
+    };
+    debug_assert!(social_network.is_directed() || social_network.is_symmetric(),
+                  "the network was built asymmetric");
+
+    // Initilize opinions
+    let opinions: Vec<Opinion> = match resume {
+        Some(checkpoint) => checkpoint.opinions.clone(),
+        None => match config.opinions {
+            Some(ref opinions) => opinions.clone(),
+            None => (0..population).map(|_| initial_opinion(config, &mut *rng)).collect()
+        }
+    };
+    social_network.set_opinions(opinions);
+
+    // Pick the zealots, who keep broadcasting their opinion but never change
+    // it.
+    let stubbornness: Vec<f64> = match resume {
+        Some(checkpoint) => checkpoint.stubbornness.clone(),
+        None => {
+            let mut stubbornness = vec![0.0; population];
+            let zealots = (config.zealots * population as f64).round() as usize;
+            if zealots > 0 {
+                for zealot in rand::sample(&mut *rng, 0..population, zealots) {
+                    stubbornness[zealot] = 1.0;
+                }
+            }
+            stubbornness
+        }
+    };
+
+    // Decide how persuadable everyone is.
+    let openness: Vec<f64> = match (resume, config.openness_distribution) {
+        (Some(checkpoint), _) => checkpoint.openness.clone(),
+        (None, OpennessDistribution::Constant) => vec![1.0; population],
+        (None, OpennessDistribution::Uniform) => {
+            (0..population).map(|_| rng.gen::<f64>()).collect()
+        }
+    };
+
+    // Let like-minded members start out closer. A pair as far apart as the
+    // opinion space allows starts with no relationship at all.
+    if config.homophilous_init && resume.is_none() {
+        let range = (config.opinion_max - config.opinion_min) *
+            (config.dimensions as f64).sqrt();
+        for edge in social_network.edges() {
+            let similarity = 1.0 - distance(social_network.opinion(edge.sender),
+                                            social_network.opinion(edge.recipient)) / range;
+            social_network.set_weight(edge.sender, edge.recipient, similarity.max(0.0));
+        }
+    }
+
+    // Store the initial state of the matrix
+    let mut edge_history: Vec<EdgeRecord> = snapshot(&social_network, first_tick);
+    let initial_stats = social_network.stats();
+    let initial_opinions = social_network.opinions().to_vec();
+    let mut opinion_history: Vec<Vec<Opinion>> = Vec::new();
+    let mut events: Vec<EdgeEvent> = Vec::new();
+    let mut edges = social_network.edges().len();
+    let mut summary = vec![summarize(first_tick, social_network.opinions(), edges)];
+    observe(&TickState {
+        tick: first_tick,
+        opinions: social_network.opinions(),
+        network: &social_network,
+        records: &edge_history,
+        summary: &summary[0],
+        last: config.ticks <= first_tick
+    });
+
+    // Simulation loop
+    // Here are the rule, every tick, we'll randomly pick a vertex and send a
+    // message to it's neighbors, messages_per_tick times over. The opinion of
+    // the message will reflect the opinions of the sender. Upon receiving the
+    // message, the opinion model decides how both opinions and the
+    // relationship change. An undirected tie is shared, so both members see
+    // it change; a directed network only changes the sender's side.
+    //
+    // Synchronous models (Hegselmann-Krause) instead update every member at
+    // once from the opinions at the end of the previous tick.
+    let mut stable_ticks = resume.map_or(0, |checkpoint| checkpoint.stable_ticks);
+    let mut converged_at = None;
+    // The mean opinion change of each of the last stable_window ticks.
+    let mut changes: VecDeque<f64> = VecDeque::with_capacity(config.stable_window + 1);
+    if let Some(checkpoint) = resume {
+        let skipped = checkpoint.changes.len().saturating_sub(config.stable_window);
+        changes.extend(&checkpoint.changes[skipped..]);
+    }
+    for tick in (first_tick + 1)..(config.ticks + 1) {
+        let previous = social_network.opinions().to_vec();
+        let counted = events.len();
+        let mut records: Vec<EdgeRecord> = Vec::new();
+
+        match model {
+            Some(ref model) => {
+                let reach = if config.reach_weighting {
+                    Some(reach(&social_network))
+                } else {
+                    None
+                };
+
+                // The broadcast has no ties of its own to change, so only its
+                // audience's opinions move.
+                if let Some(ref media) = config.media {
+                    let message = vec![media.opinion; config.dimensions];
+                    let audience = (media.reach * population as f64).round() as usize;
+                    for member in rand::sample(&mut *rng, 0..population, audience) {
+                        let opinion = social_network.opinion(member).clone();
+                        let outcome = model.interact(&message, &message, &opinion,
+                                                     media.strength);
+                        let moved = scale_change(&opinion, outcome.recipient, openness[member]);
+                        social_network.set_opinion(member, resist(&opinion, moved,
+                                                                  stubbornness[member],
+                                                                  config.inertia));
+                    }
+                }
+
+                let stddev = config.message_stddev_at(tick);
+                for _ in 0..config.messages_per_tick {
+                    let sender = rng.sender(population);
+                    let message: Opinion = social_network.opinion(sender).iter()
+                        .map(|&opinion| rng.message(opinion, stddev))
+                        .collect();
+
+                    // Every outcome is worked out from the opinions as they
+                    // stood when the broadcast began and applied in order
+                    // below. Changes to the sender are applied as offsets so
+                    // they still add up across recipients, and a run comes
+                    // out the same with or without threads.
+                    let start = social_network.opinion(sender).clone();
+                    let outcomes = social_network.broadcast(sender, &message, &**model,
+                                                            config.mutual, pool.as_ref());
+                    for (recipient, strength, mut outcome) in outcomes {
+                        let current = social_network.opinion(sender).clone();
+                        let heard = social_network.opinion(recipient).clone();
+                        if outcome.sender != start {
+                            outcome.sender = current.iter().zip(&outcome.sender).zip(&start)
+                                .map(|((current, moved), before)| current + (moved - before))
+                                .collect();
+                        } else {
+                            outcome.sender = current.clone();
+                        }
+                        if strength.abs() < config.min_active_weight {
+                            outcome.sender = current.clone();
+                            outcome.recipient = heard.clone();
+                        }
+                        if let Some(ref reach) = reach {
+                            outcome.recipient = scale_change(&heard, outcome.recipient,
+                                                             reach[sender]);
+                        }
+                        outcome.recipient = scale_change(&heard, outcome.recipient,
+                                                         openness[recipient]);
+                        social_network.set_opinion(sender, resist(&current, outcome.sender,
+                                                                  stubbornness[sender],
+                                                                  config.inertia));
+                        social_network.set_opinion(recipient, resist(&heard, outcome.recipient,
+                                                                     stubbornness[recipient],
+                                                                     config.inertia));
+                        // The tie that changes is the sender's to the
+                        // recipient, unless grudges make a recipient that
+                        // was offended sour on the sender by the same amount.
+                        let (holder, target, old, new) = if config.grudges &&
+                                outcome.strength < strength {
+                            let old = social_network.weight(recipient, sender);
+                            let floor = if config.signed { -1.0 } else { 0.0 };
+                            let soured = old - (strength - outcome.strength);
+                            (recipient, sender, old, soured.max(floor))
+                        } else {
+                            (sender, recipient, strength, outcome.strength)
+                        };
+                        if new != old {
+                            social_network.set_weight(holder, target, new);
+                            events.extend(edge_event(tick, holder, target, old, new));
+                        }
+
+                        // A severed tie is replaced by a new one to someone
+                        // closer, which is how echo chambers form over time.
+                        if config.rewire_on_conflict && new == 0.0 && old != 0.0 {
+                            let friend = like_minded(&social_network, recipient, sender,
+                                                     config.consensus, &mut *rng);
+                            if let Some(friend) = friend {
+                                let mut pairs = vec![(recipient, friend)];
+                                if social_network.is_directed() {
+                                    pairs.push((friend, recipient));
+                                }
+                                // The friend may already have a tie of its own
+                                // towards the recipient, which stays as it is.
+                                for (from, to) in pairs {
+                                    let existing = social_network.weight(from, to);
+                                    if existing != 0.0 {
+                                        continue;
+                                    }
+                                    social_network.set_weight(from, to, config.initial_weight);
+                                    events.extend(edge_event(tick, from, to, existing,
+                                                             config.initial_weight));
+                                    records.push(EdgeRecord {
+                                        tick,
+                                        sender: from,
+                                        recipient: to,
+                                        weight: config.initial_weight
+                                    });
+                                }
+                            }
+                        }
+
+                        // Only record relationships that moved unless every
+                        // edge was asked for.
+                        let weight = social_network.weight(holder, target);
+                        if config.full_network_dump || weight != old {
+                            records.push(EdgeRecord {
+                                tick,
+                                sender: holder,
+                                recipient: target,
+                                weight
+                            });
+                        }
+                    }
+                }
+            },
+            None => {
+                // Synchronous models never touch relationships, so there is
+                // only something to record for a full dump.
+                let updated = hegselmann_krause(&social_network, social_network.opinions(),
+                                                config.confidence_bound);
+                let opinions = updated.into_iter().enumerate()
+                    .map(|(node, opinion)| {
+                        let old = social_network.opinion(node);
+                        let opinion = scale_change(old, opinion, openness[node]);
+                        resist(old, opinion, stubbornness[node], config.inertia)
+                    })
+                    .collect();
+                social_network.set_opinions(opinions);
+                if config.full_network_dump {
+                    records = snapshot(&social_network, tick);
+                }
+            }
+        }
+
+        // Cleanup opinions to be within the opinion range
+        social_network.clamp_opinions(config.boundary, config.opinion_min, config.opinion_max);
+        for event in &events[counted..] {
+            match event.kind {
+                EdgeEventKind::Formed => edges += 1,
+                EdgeEventKind::Removed => edges -= 1
+            }
+        }
+        summary.push(summarize(tick, social_network.opinions(), edges));
+        debug_assert!(tick % SYMMETRY_CHECK_INTERVAL != 0 || social_network.is_directed() ||
+                      social_network.is_symmetric(),
+                      "the network became asymmetric by tick {}", tick);
+
+        let moved: Vec<f64> = previous.iter().zip(social_network.opinions())
+            .map(|(before, after)| distance(before, after))
+            .collect();
+        if tick >= config.burn_in {
+            changes.push_back(moved.iter().sum::<f64>() / population as f64);
+            if changes.len() > config.stable_window {
+                changes.pop_front();
+            }
+        }
+
+        // Stop early once no opinion has moved by more than epsilon for a
+        // whole window of ticks from the burn-in on.
+        if let (Some(epsilon), true) = (config.convergence_epsilon, tick >= config.burn_in) {
+            let change = moved.iter().cloned().fold(0.0_f64, f64::max);
+            if change < epsilon {
+                stable_ticks += 1;
+            } else {
+                stable_ticks = 0;
+            }
+            if stable_ticks >= config.stable_window {
+                converged_at = Some(tick);
+            }
+        }
+
+        observe(&TickState {
+            tick,
+            opinions: social_network.opinions(),
+            network: &social_network,
+            records: &records,
+            summary: &summary[summary.len() - 1],
+            last: converged_at.is_some() || tick == config.ticks
+        });
+        if config.record_history {
+            opinion_history.push(social_network.opinions().to_vec());
+            edge_history.extend(records);
+        }
+        if converged_at.is_some() {
+            break;
+        }
+
+        // A checkpoint that cannot be written should not cost the run itself.
+        let due = config.checkpoint_interval.is_some_and(|interval| tick % interval == 0);
+        if let (true, Some(draws)) = (due, rng.draws()) {
+            let checkpoint = Checkpoint {
+                tick,
+                draws,
+                stable_ticks,
+                changes: changes.iter().cloned().collect(),
+                directed: social_network.is_directed(),
+                opinions: social_network.opinions().to_vec(),
+                stubbornness: stubbornness.clone(),
+                openness: openness.clone(),
+                positions: positions.clone(),
+                edges: social_network.edges()
+            };
+            if let Err(e) = checkpoint.save(&config.checkpoint_path) {
+                let _ = writeln!(&mut io::stderr(), "Unable to write checkpoint {}: {}",
+                                 config.checkpoint_path, e);
+            }
+        }
+    }
+
+    SimulationResult {
+        opinions: social_network.opinions().to_vec(),
+        network: social_network,
+        first_tick,
+        initial_stats,
+        positions,
+        stubbornness,
+        openness,
+        initial_opinions,
+        opinion_history,
+        edge_history,
+        events,
+        summary,
+        converged_at,
+        oscillating: converged_at.is_none() && changes.len() == config.stable_window &&
+                     oscillating(&changes)
+    }
+}
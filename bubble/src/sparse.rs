@@ -0,0 +1,83 @@
+use std;
+use std::collections::BTreeMap;
+
+use adjacency::Adjacency;
+use matrix::Matrix;
+
+/// A symmetric adjacency structure that only stores connected pairs, for
+/// populations too large for a dense `Matrix`. Rows are ordered maps so that
+/// neighbours are visited in the same order as the dense storage and seeded
+/// runs stay reproducible.
+#[derive(Debug, Clone)]
+pub struct SparseMatrix<T> {
+    size: usize,
+    rows: Vec<BTreeMap<usize, T>>
+}
+
+impl<T> SparseMatrix<T> where T: Default + Copy + PartialEq {
+    pub fn new(size: usize) -> Self {
+        SparseMatrix {
+            size,
+            rows: vec![BTreeMap::new(); size]
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> T {
+        assert!(col < self.size, "column {} out of bounds", col);
+        self.rows[row].get(&col).cloned().unwrap_or_default()
+    }
+
+    /// Store `value` symmetrically. Storing the default value removes the
+    /// pair entirely.
+    pub fn put(&mut self, row: usize, col: usize, value: T) {
+        assert!(row < self.size && col < self.size,
+                "cell ({}, {}) out of bounds", row, col);
+        if value == T::default() {
+            self.rows[row].remove(&col);
+            self.rows[col].remove(&row);
+        } else {
+            self.rows[row].insert(col, value);
+            self.rows[col].insert(row, value);
+        }
+    }
+}
+
+impl<T> Adjacency<T> for SparseMatrix<T> where T: Default + Copy + PartialEq {
+    fn new(size: usize) -> Self {
+        SparseMatrix::new(size)
+    }
+
+    fn size(&self) -> usize {
+        SparseMatrix::size(self)
+    }
+
+    fn get(&self, row: usize, col: usize) -> T {
+        SparseMatrix::get(self, row, col)
+    }
+
+    fn put(&mut self, row: usize, col: usize, value: T) {
+        SparseMatrix::put(self, row, col, value)
+    }
+
+    fn neighbors(&self, node: usize) -> Vec<(usize, T)> {
+        self.rows[node].iter().map(|(&col, &value)| (col, value)).collect()
+    }
+}
+
+/// Keep only the connected pairs of a dense matrix.
+impl<T> From<Matrix<T>> for SparseMatrix<T>
+    where T: Default + Copy + std::fmt::Display + std::cmp::PartialEq {
+    fn from(matrix: Matrix<T>) -> Self {
+        let mut sparse = SparseMatrix::new(matrix.size());
+        for row in 0..matrix.size() {
+            for (col, value) in matrix.neighbors(row) {
+                sparse.put(row, col, value);
+            }
+        }
+        sparse
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+
+use model::{distance, Opinion};
+
+/// The arithmetic mean of `values`. Zero when there are none.
+pub fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// The population variance of `values`: the mean squared distance from their
+/// mean. Zero when there are none.
+pub fn variance(values: &[f64]) -> f64 {
+    let centre = mean(values);
+    mean(&values.iter().map(|value| (value - centre) * (value - centre)).collect::<Vec<f64>>())
+}
+
+// Every member's coordinate in one dimension.
+fn coordinates(opinions: &[Opinion], dimension: usize) -> Vec<f64> {
+    opinions.iter().map(|opinion| opinion[dimension]).collect()
+}
+
+/// The mean of a non-empty set of opinions, taken per dimension.
+pub fn mean_opinion(opinions: &[Opinion]) -> Opinion {
+    (0..opinions[0].len())
+        .map(|dimension| mean(&coordinates(opinions, dimension)))
+        .collect()
+}
+
+/// How divided a population is: the mean squared distance of every opinion
+/// from the population's mean opinion, which is the sum of the variances of
+/// each dimension.
+pub fn polarization(opinions: &[Opinion]) -> f64 {
+    if opinions.is_empty() {
+        return 0.0;
+    }
+
+    (0..opinions[0].len())
+        .map(|dimension| variance(&coordinates(opinions, dimension)))
+        .sum()
+}
+
+// The bucket `value` falls in when [min, max] is split into `bins` equal
+// buckets. The top of the range belongs to the last bucket.
+fn bin(value: f64, bins: usize, min: f64, max: f64) -> usize {
+    (((value - min) / (max - min) * bins as f64) as usize).min(bins - 1)
+}
+
+/// How many of `values` fall in each of `bins` equal buckets over [min, max].
+pub fn histogram(values: &[f64], bins: usize, min: f64, max: f64) -> Vec<usize> {
+    let mut counts = vec![0; bins];
+    for &value in values {
+        counts[bin(value, bins, min, max)] += 1;
+    }
+    counts
+}
+
+/// The Shannon entropy, in bits, of the opinions binned into `bins` equal
+/// buckets per dimension over [min, max]. Zero when everyone shares a bucket;
+/// the more evenly spread over more buckets, the higher.
+pub fn opinion_entropy(opinions: &[Opinion], bins: usize, min: f64, max: f64) -> f64 {
+    let mut counts: HashMap<Vec<usize>, usize> = HashMap::new();
+    for opinion in opinions {
+        let cell: Vec<usize> = opinion.iter().map(|&value| bin(value, bins, min, max)).collect();
+        *counts.entry(cell).or_insert(0) += 1;
+    }
+
+    let count = opinions.len() as f64;
+    counts.values()
+        .map(|&members| {
+            let share = members as f64 / count;
+            -share * share.log2()
+        })
+        .sum()
+}
+
+/// A group of members holding similar opinions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpinionCluster {
+    pub mean: Opinion,
+    pub size: usize
+}
+
+/// Group opinions by single linkage: two members share a cluster whenever a
+/// chain of members links them with every step closer than `threshold`. For
+/// a single dimension this splits the sorted opinions at every gap of at least
+/// `threshold`. Clusters are ordered by their mean's first coordinate.
+pub fn opinion_clusters(opinions: &[Opinion], threshold: f64) -> Vec<OpinionCluster> {
+    let n = opinions.len();
+    let mut labels: Vec<Option<usize>> = vec![None; n];
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+
+    if opinions.first().is_some_and(|opinion| opinion.len() == 1) {
+        let mut order: Vec<usize> = (0..n).collect();
+        order.sort_by(|&a, &b| opinions[a][0].partial_cmp(&opinions[b][0]).unwrap());
+        for (index, &node) in order.iter().enumerate() {
+            if index == 0 || opinions[node][0] - opinions[order[index - 1]][0] >= threshold {
+                groups.push(Vec::new());
+            }
+            groups.last_mut().unwrap().push(node);
+        }
+    } else {
+        // Flood fill over the "closer than threshold" relation.
+        for start in 0..n {
+            if labels[start].is_some() {
+                continue;
+            }
+            let label = groups.len();
+            let mut group = vec![start];
+            labels[start] = Some(label);
+            let mut index = 0;
+            while index < group.len() {
+                let node = group[index];
+                for other in 0..n {
+                    if labels[other].is_none() && distance(&opinions[node], &opinions[other]) < threshold {
+                        labels[other] = Some(label);
+                        group.push(other);
+                    }
+                }
+                index += 1;
+            }
+            groups.push(group);
+        }
+    }
+
+    let mut clusters: Vec<OpinionCluster> = groups.iter()
+        .map(|group| {
+            let members: Vec<Opinion> = group.iter().map(|&node| opinions[node].clone()).collect();
+            OpinionCluster { mean: mean_opinion(&members), size: group.len() }
+        })
+        .collect();
+    clusters.sort_by(|a, b| a.mean[0].partial_cmp(&b.mean[0]).unwrap());
+    clusters
+}
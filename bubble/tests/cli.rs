@@ -0,0 +1,103 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// The binary Cargo builds next to the directory holding this test.
+fn binary() -> PathBuf {
+    let mut path = env::current_exe().unwrap();
+    path.pop();
+    if path.ends_with("deps") {
+        path.pop();
+    }
+    path.join("bubbles")
+}
+
+// Run the binary with `args` and return what it printed on stderr, failing
+// unless it refused to run. The positional arguments follow `--` so that
+// negative values are not taken for flags, which therefore go first.
+fn rejected(args: &[&str]) -> String {
+    let (flags, positional): (Vec<&str>, Vec<&str>) = args.iter()
+        .partition(|arg| arg.starts_with("--"));
+    let output = Command::new(binary())
+        .args(&flags)
+        .arg("--")
+        .args(&positional)
+        .current_dir(env::temp_dir())
+        .output()
+        .expect("unable to run bubbles");
+    assert!(!output.status.success(), "{:?} was accepted", args);
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+// Run the binary with `args` in `dir`, failing unless it succeeded.
+fn accepted(dir: &Path, args: &[&str]) {
+    fs::create_dir_all(dir).unwrap();
+    let status = Command::new(binary())
+        .args(args)
+        .current_dir(dir)
+        .status()
+        .expect("unable to run bubbles");
+    assert!(status.success(), "{:?} was rejected", args);
+}
+
+// The first line after the header of a file in `dir`, split into its fields.
+fn first_row(dir: &Path, name: &str) -> Vec<String> {
+    let contents = fs::read_to_string(dir.join(name)).unwrap();
+    contents.lines().nth(1).unwrap().split(',').map(|field| field.to_string()).collect()
+}
+
+#[test]
+fn invalid_watts_strogatz_parameters_are_rejected() {
+    for degree in &["20", "24"] {
+        let error = rejected(&["20", degree, "0.1", "20", "60"]);
+        assert!(error.contains("<degree> to be smaller than <population>"), "{}", error);
+    }
+    let error = rejected(&["20", "5", "0.1", "20", "60"]);
+    assert!(error.contains("<degree> must be even"), "{}", error);
+    let error = rejected(&["1", "0", "0.1", "20", "60"]);
+    assert!(error.contains("<population> must be at least 2"), "{}", error);
+}
+
+#[test]
+fn out_of_range_probabilities_and_thresholds_are_rejected() {
+    for rewire in &["-0.1", "1.5", "NaN"] {
+        let error = rejected(&["20", "4", rewire, "20", "60"]);
+        assert!(error.contains("<rewire> is a probability"), "{}", error);
+    }
+    for &(consensus, opposition) in &[("-1", "60"), ("20", "-60")] {
+        let error = rejected(&["20", "4", "0.1", consensus, opposition]);
+        assert!(error.contains("must not be negative"), "{}", error);
+    }
+}
+
+// With a burn-in of 5 the aggregated outputs start at tick 5, and a run whose
+// every tick is quiet converges there too, after a window of one tick.
+#[test]
+fn burn_in_skips_the_same_ticks_everywhere() {
+    let dir = env::temp_dir().join("bubbles_cli_burn_in");
+    accepted(&dir, &["20", "4", "0.1", "20", "60", "--seed=3", "--ticks=20", "--burn-in=5",
+                     "--summary=summary.csv", "--polarization=polarization.csv",
+                     "--entropy=entropy.csv", "--convergence-epsilon=1000",
+                     "--stable-window=1", "--prefix=run"]);
+
+    for name in &["summary.csv", "polarization.csv", "entropy.csv"] {
+        assert_eq!(first_row(&dir, name)[0], "5", "{}", name);
+    }
+    let contents = fs::read_to_string(dir.join("run_metadata.csv")).unwrap();
+    let mut lines = contents.lines();
+    let columns: Vec<&str> = lines.next().unwrap().split(',').collect();
+    let values: Vec<&str> = lines.next().unwrap().split(',').collect();
+    let converged_at = columns.iter().position(|&column| column == "converged_at").unwrap();
+    assert_eq!(values[converged_at], "5");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn negative_and_nan_radii_are_rejected() {
+    for radius in &["-0.1", "NaN"] {
+        let error = rejected(&["20", "4", "0.1", "20", "60", "--topology=geometric",
+                               &format!("--radius={}", radius)]);
+        assert!(error.contains("--radius must not be negative"), "{}", error);
+    }
+}
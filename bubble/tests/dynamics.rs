@@ -0,0 +1,263 @@
+extern crate bubbles;
+extern crate rand;
+
+use std::collections::VecDeque;
+
+use bubbles::{simulate, simulate_with, DirectedMatrix, EdgeEventKind, Matrix, Opinion,
+              SimulationConfig, SimulationResult, SimulationRng};
+use rand::{Rng, SeedableRng, StdRng};
+
+// Plays back a fixed list of senders and sends every opinion without noise,
+// so each tick can be worked out by hand.
+struct ScriptedRng {
+    rng: StdRng,
+    senders: VecDeque<usize>
+}
+
+impl Rng for ScriptedRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+}
+
+impl SimulationRng for ScriptedRng {
+    fn sender(&mut self, _: usize) -> usize {
+        self.senders.pop_front().expect("the script ran out of senders")
+    }
+
+    fn message(&mut self, opinion: f64, _: f64) -> f64 {
+        opinion
+    }
+}
+
+// Three members, all connected with a strength of 0.5, on the default
+// [0, 100] scale with a consensus threshold of 20 and opposition of 60. One
+// tick per scripted sender, each broadcasting once.
+fn config(opinions: &[f64], ticks: usize) -> SimulationConfig {
+    let mut matrix = Matrix::new(3);
+    for &(a, b) in &[(0, 1), (0, 2), (1, 2)] {
+        matrix.put(a, b, 0.5);
+    }
+    let mut config = SimulationConfig::new(3, 2, 0.0, 20.0, 60.0, 1);
+    config.network = Some(matrix.into());
+    config.opinions = Some(opinions.iter().map(|&opinion| vec![opinion]).collect());
+    config.ticks = ticks;
+    config
+}
+
+fn run(config: &SimulationConfig, senders: &[usize]) -> SimulationResult {
+    let mut rng = ScriptedRng {
+        rng: SeedableRng::from_seed(&[1][..]),
+        senders: senders.iter().cloned().collect()
+    };
+    simulate_with(config, &mut rng, |_| {})
+}
+
+fn assert_close(actual: f64, expected: f64) {
+    assert!((actual - expected).abs() < 1e-9, "expected {}, found {}", expected, actual);
+}
+
+fn assert_opinions(actual: &[Opinion], expected: &[f64]) {
+    assert_eq!(actual.len(), expected.len());
+    for (opinion, &expected) in actual.iter().zip(expected) {
+        assert_close(opinion[0], expected);
+    }
+}
+
+// Messages within the consensus threshold strengthen the tie by the margin
+// left below the threshold and pull the recipient towards the message by the
+// strength of the tie before the message, in hundredths of the gap.
+#[test]
+fn consensus_pulls_recipients_in_and_strengthens_ties() {
+    let result = run(&config(&[50.0, 55.0, 60.0], 1), &[0]);
+
+    // 55 + 0.5 * (50 - 55) / 100 and 60 + 0.5 * (50 - 60) / 100.
+    assert_opinions(&result.opinions, &[50.0, 54.975, 59.95]);
+    // 0.5 + (20 - 5) / 100 and 0.5 + (20 - 10) / 100.
+    assert_close(result.network.weight(0, 1), 0.65);
+    assert_close(result.network.weight(0, 2), 0.6);
+    assert_close(result.network.weight(1, 2), 0.5);
+}
+
+// Between the thresholds the tie is left alone. Without neutral drift nothing
+// moves; with it recipients drift that share of the usual pull.
+#[test]
+fn neutral_band_leaves_ties_and_only_drifts() {
+    let result = run(&config(&[50.0, 80.0, 20.0], 1), &[0]);
+    assert_opinions(&result.opinions, &[50.0, 80.0, 20.0]);
+
+    let mut drifting = config(&[50.0, 80.0, 20.0], 1);
+    drifting.neutral_drift = 0.5;
+    let result = run(&drifting, &[0]);
+
+    // 80 + 0.5 * 0.5 * (50 - 80) / 100 and 20 + 0.5 * 0.5 * (50 - 20) / 100.
+    assert_opinions(&result.opinions, &[50.0, 79.925, 20.075]);
+    for &(a, b) in &[(0, 1), (0, 2), (1, 2)] {
+        assert_close(result.network.weight(a, b), 0.5);
+    }
+}
+
+// Messages beyond the opposition threshold weaken the tie by how far past
+// the threshold they land and push the recipient away from the message.
+#[test]
+fn opposition_pushes_recipients_away_and_weakens_ties() {
+    let result = run(&config(&[80.0, 10.0, 90.0], 1), &[1]);
+
+    // 80 - 0.5 * (10 - 80) / 100 and 90 - 0.5 * (10 - 90) / 100.
+    assert_opinions(&result.opinions, &[80.35, 10.0, 90.4]);
+    // 0.5 - (70 - 60) / 100 and 0.5 - (80 - 60) / 100.
+    assert_close(result.network.weight(0, 1), 0.4);
+    assert_close(result.network.weight(1, 2), 0.3);
+    assert_close(result.network.weight(0, 2), 0.5);
+}
+
+// Each member broadcasts once in turn. Every step below was worked out from
+// the rules above, using the opinions and ties left by the previous message.
+#[test]
+fn three_ticks_follow_the_worked_trajectory() {
+    let mut config = config(&[20.0, 30.0, 90.0], 3);
+    config.record_history = true;
+    let result = run(&config, &[0, 1, 2]);
+
+    // Tick 1, member 0 says 20: member 1 agrees, member 2 opposes.
+    assert_opinions(&result.opinion_history[0], &[20.0, 29.95, 90.35]);
+    // Tick 2, member 1 says 29.95: member 0 agrees over a tie of 0.6,
+    // member 2 is 60.4 away and opposes.
+    assert_opinions(&result.opinion_history[1], &[20.0597, 29.95, 90.652]);
+    // Tick 3, member 2 says 90.652: both others oppose.
+    assert_opinions(&result.opinion_history[2], &[19.7773308, 29.64891808, 90.652]);
+    assert_opinions(&result.opinions, &[19.7773308, 29.64891808, 90.652]);
+
+    assert_close(result.network.weight(0, 1), 0.7005);
+    assert_close(result.network.weight(0, 2), 0.294077);
+    assert_close(result.network.weight(1, 2), 0.48898);
+    assert_eq!(result.converged_at, None);
+}
+
+// Member 0 is tied to each of three leaves at 0.5, so its strength of 1.5 is
+// twice the mean of 0.75 and each leaf's 0.5 is two thirds of it.
+fn star(opinions: &[f64]) -> SimulationConfig {
+    let mut matrix = Matrix::new(4);
+    for leaf in 1..4 {
+        matrix.put(0, leaf, 0.5);
+    }
+    let mut config = SimulationConfig::new(4, 2, 0.0, 20.0, 60.0, 1);
+    config.network = Some(matrix.into());
+    config.opinions = Some(opinions.iter().map(|&opinion| vec![opinion]).collect());
+    config.ticks = 1;
+    config.reach_weighting = true;
+    config
+}
+
+// The same gap over the same tie moves a leaf listening to the hub further
+// than it moves the hub listening to a leaf.
+#[test]
+fn reach_weighting_lets_hubs_move_opinions_more() {
+    let config = star(&[50.0, 55.0, 55.0, 55.0]);
+    let from_hub = run(&config, &[0]);
+    let from_leaf = run(&config, &[1]);
+
+    // 55 + 2 * 0.5 * (50 - 55) / 100 and 50 + 2 / 3 * 0.5 * (55 - 50) / 100.
+    assert_close(from_hub.opinions[1][0], 54.95);
+    assert_close(from_leaf.opinions[0][0], 50.0 + 0.05 / 3.0);
+    assert!(55.0 - from_hub.opinions[1][0] > from_leaf.opinions[0][0] - 50.0);
+
+    let mut unweighted = config.clone();
+    unweighted.reach_weighting = false;
+    assert_close(run(&unweighted, &[0]).opinions[1][0], 54.975);
+    assert_close(run(&unweighted, &[1]).opinions[0][0], 50.025);
+}
+
+// The same run on a [0, 1] scale, with every threshold and spread divided by
+// 100, lands on the same opinions divided by 100 and the same ties.
+#[test]
+fn rescaling_the_opinion_range_rescales_the_run() {
+    let mut percent = SimulationConfig::new(30, 4, 0.1, 20.0, 60.0, 8);
+    percent.ticks = 300;
+    let mut unit = percent.clone();
+    unit.opinion_max = 1.0;
+    unit.consensus = 0.2;
+    unit.opposition = 0.6;
+    unit.init_mean = 0.5;
+    unit.init_stddev = 0.1;
+    unit.message_stddev = 0.1;
+    unit.final_message_stddev = 0.01;
+
+    let percent = simulate(&percent);
+    let unit = simulate(&unit);
+    assert_eq!(unit.network.edges().len(), percent.network.edges().len());
+    for (unit, percent) in unit.opinions.iter().zip(&percent.opinions) {
+        assert!((unit[0] - percent[0] / 100.0).abs() < 1e-9, "{} and {}", unit[0], percent[0]);
+    }
+    for (unit, percent) in unit.network.edges().iter().zip(&percent.network.edges()) {
+        assert_eq!((unit.sender, unit.recipient), (percent.sender, percent.recipient));
+        assert_close(unit.weight, percent.weight);
+    }
+}
+
+// A directed network of members holding `opinions` and tied by `ties` of
+// (sender, recipient, strength).
+fn directed(opinions: &[f64], ties: &[(usize, usize, f64)]) -> SimulationConfig {
+    let mut matrix = DirectedMatrix::new(opinions.len());
+    for &(sender, recipient, strength) in ties {
+        matrix.put(sender, recipient, strength);
+    }
+    let mut config = SimulationConfig::new(opinions.len(), 2, 0.0, 20.0, 60.0, 1);
+    config.directed = true;
+    config.network = Some(matrix.into());
+    config.opinions = Some(opinions.iter().map(|&opinion| vec![opinion]).collect());
+    config.ticks = 1;
+    config
+}
+
+// A tie running from 0 to 1 carries 0's messages to 1 and nothing back.
+#[test]
+fn directed_ties_carry_influence_one_way() {
+    let config = directed(&[50.0, 55.0], &[(0, 1, 0.5)]);
+
+    let result = run(&config, &[1]);
+    assert_opinions(&result.opinions, &[50.0, 55.0]);
+    assert_eq!((result.network.weight(0, 1), result.network.weight(1, 0)), (0.5, 0.0));
+
+    let result = run(&config, &[0]);
+    assert_opinions(&result.opinions, &[50.0, 54.975]);
+    assert_close(result.network.weight(0, 1), 0.65);
+    assert_eq!(result.network.weight(1, 0), 0.0);
+}
+
+// Member 1's message lands 70 away from member 0, 10 past the opposition
+// threshold. Normally the sender's tie weakens; with grudges the offended
+// recipient's tie back to the sender does instead.
+#[test]
+fn grudges_weaken_only_the_offended_direction() {
+    let mut config = directed(&[80.0, 10.0], &[(0, 1, 0.5), (1, 0, 0.5)]);
+    let result = run(&config, &[1]);
+    assert_opinions(&result.opinions, &[80.35, 10.0]);
+    assert_close(result.network.weight(1, 0), 0.4);
+    assert_close(result.network.weight(0, 1), 0.5);
+
+    config.grudges = true;
+    let result = run(&config, &[1]);
+    assert_opinions(&result.opinions, &[80.35, 10.0]);
+    assert_close(result.network.weight(1, 0), 0.5);
+    assert_close(result.network.weight(0, 1), 0.4);
+}
+
+// Member 1's message cuts its weak tie to member 0, who befriends member 2.
+// Member 2 already thinks highly of member 0, and keeps doing so: only the new
+// tie from 0 to 2 is formed.
+#[test]
+fn rewiring_keeps_a_tie_the_friend_already_has() {
+    let mut config = directed(&[80.0, 10.0, 75.0], &[(1, 0, 0.1), (2, 0, 0.9)]);
+    config.rewire_on_conflict = true;
+    let result = run(&config, &[1]);
+
+    assert_eq!(result.network.weight(1, 0), 0.0);
+    assert_eq!(result.network.weight(0, 2), 0.5);
+    assert_eq!(result.network.weight(2, 0), 0.9);
+    let formed: Vec<(usize, usize)> = result.events.iter()
+        .filter(|event| event.kind == EdgeEventKind::Formed)
+        .map(|event| (event.sender, event.recipient))
+        .collect();
+    assert_eq!(formed, vec![(0, 2)]);
+}
@@ -0,0 +1,70 @@
+extern crate bubbles;
+extern crate rand;
+
+use bubbles::{Adjacency, Matrix};
+use rand::{SeedableRng, StdRng};
+
+fn rng(seed: usize) -> StdRng {
+    SeedableRng::from_seed(&[seed][..])
+}
+
+// Rewiring moves edges without adding or dropping any, and a beta of 0 leaves
+// the ring lattice exactly as it was.
+#[test]
+fn watts_strogatz_keeps_the_lattice_edge_count() {
+    let lattice: Matrix<f64> = Matrix::ring_lattice(20, 6, 1.0);
+    for &beta in &[0.0, 0.3, 1.0] {
+        let matrix: Matrix<f64> = Matrix::wattz_strogatz(20, 6, beta, 1.0, &mut rng(3));
+        assert_eq!(matrix.edge_count(), 60, "beta {}", beta);
+    }
+
+    let unchanged: Matrix<f64> = Matrix::wattz_strogatz(20, 6, 0.0, 1.0, &mut rng(3));
+    for node in 0..20 {
+        assert_eq!(Adjacency::neighbors(&unchanged, node), Adjacency::neighbors(&lattice, node));
+    }
+}
+
+// Every member after the seed clique of m brings exactly m new edges.
+#[test]
+fn barabasi_albert_adds_m_edges_per_member() {
+    for &(n, m) in &[(50, 1), (50, 3), (30, 5)] {
+        let matrix: Matrix<f64> = Matrix::barabasi_albert(n, m, 1.0, &mut rng(1));
+        assert_eq!(matrix.edge_count(), m * (n - m) + m * (m - 1) / 2, "n {} m {}", n, m);
+    }
+}
+
+// Averaged over many seeded draws, the share of connected pairs settles on p.
+#[test]
+fn erdos_renyi_density_converges_to_p() {
+    let mut rng = rng(4);
+    let trials = 200;
+    let total: f64 = (0..trials)
+        .map(|_| Matrix::<f64>::erdos_renyi(40, 0.2, 1.0, &mut rng).density())
+        .sum();
+    let mean = total / trials as f64;
+    assert!((mean - 0.2).abs() < 0.01, "mean density {}", mean);
+}
+
+// Before any rewiring every member holds k markers, k/2 on either side.
+#[test]
+fn ring_lattice_rows_sum_to_k() {
+    for &(n, k) in &[(10, 2), (10, 4), (7, 6)] {
+        let matrix: Matrix<f64> = Matrix::ring_lattice(n, k, 1.0);
+        for row in 0..n {
+            let sum: f64 = (0..n).map(|col| matrix.get(row, col)).sum();
+            assert_eq!(sum, k as f64, "n {} k {} row {}", n, k, row);
+        }
+    }
+}
+
+// A degree of 0 asks for isolated members: no lattice edges, and nothing for
+// rewiring to move.
+#[test]
+fn zero_degree_builds_an_empty_network() {
+    let lattice: Matrix<f64> = Matrix::ring_lattice(10, 0, 1.0);
+    assert_eq!(lattice.edge_count(), 0);
+    for &beta in &[0.0, 0.5, 1.0] {
+        let matrix: Matrix<f64> = Matrix::wattz_strogatz(10, 0, beta, 1.0, &mut rng(2));
+        assert_eq!(matrix.edge_count(), 0, "beta {}", beta);
+    }
+}
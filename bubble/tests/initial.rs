@@ -0,0 +1,35 @@
+extern crate bubbles;
+
+use bubbles::{simulate_observed, SimulationConfig};
+
+// The mean of every starting opinion of a large population, taken before the
+// first tick.
+fn initial_mean(init_mean: f64, init_stddev: f64) -> f64 {
+    let mut config = SimulationConfig::new(2000, 4, 0.1, 20.0, 60.0, 9);
+    config.sparse = true;
+    config.dimensions = 5;
+    config.ticks = 1;
+    config.init_mean = init_mean;
+    config.init_stddev = init_stddev;
+
+    let mut mean = None;
+    simulate_observed(&config, |state| {
+        if mean.is_none() {
+            let values: Vec<f64> = state.opinions.iter().flat_map(|o| o.clone()).collect();
+            mean = Some(values.iter().sum::<f64>() / values.len() as f64);
+        }
+    });
+    mean.unwrap()
+}
+
+// A normal distribution cut to [0, 100] keeps the mean of its centre, while
+// one peaking near 0 loses its left tail. For a mean of 10 and deviation of 20
+// the truncated mean is 10 + 20 (φ(-0.5) - φ(4.5)) / (Φ(4.5) - Φ(-0.5)) =
+// 20.18; folding the tail back in with abs() would give 17.91.
+#[test]
+fn initial_opinions_follow_the_truncated_normal() {
+    let centred = initial_mean(50.0, 30.0);
+    assert!((centred - 50.0).abs() < 0.75, "mean {}", centred);
+    let skewed = initial_mean(10.0, 20.0);
+    assert!((skewed - 20.18).abs() < 0.75, "mean {}", skewed);
+}
@@ -0,0 +1,223 @@
+extern crate bubbles;
+extern crate rand;
+extern crate rayon;
+
+use std::env;
+use std::fs::File;
+use std::io::Write;
+
+use bubbles::{Adjacency, Boundary, ConfidenceBound, ConvergenceRate, DeffuantModel,
+              DirectedMatrix, Matrix, Network, SparseMatrix};
+use rand::{SeedableRng, StdRng};
+use rayon::{Configuration, ThreadPool};
+
+fn assert_close(actual: f64, expected: f64) {
+    assert!((actual - expected).abs() < 1e-9, "expected {}, found {}", expected, actual);
+}
+
+// Write `contents` to a file of its own and return its path.
+fn edge_list(name: &str, contents: &str) -> String {
+    let path = env::temp_dir().join(format!("bubbles_{}.csv", name));
+    File::create(&path).and_then(|mut file| file.write_all(contents.as_bytes())).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+#[test]
+fn edge_lists_load_into_any_storage() {
+    let path = edge_list("edges", "source,target,weight\n0,1,0.5\n\n2,3\n3,0,0.25\n");
+    let dense = Matrix::from_edge_list(&path, 4, 1.0).unwrap();
+    let sparse = SparseMatrix::from_edge_list(&path, 4, 1.0).unwrap();
+
+    for node in 0..4 {
+        assert_eq!(sparse.neighbors(node), Adjacency::neighbors(&dense, node));
+    }
+    assert_eq!(sparse.neighbors(3), vec![(0, 0.25), (2, 1.0)]);
+}
+
+#[test]
+fn edge_lists_reject_self_loops() {
+    let path = edge_list("self_loop", "0,1\n2,2\n");
+    let error = SparseMatrix::<f64>::from_edge_list(&path, 3, 1.0).unwrap_err();
+    assert_eq!(error.to_string(), format!("{}:2: member 2 cannot be connected to itself", path));
+    assert!(Matrix::<f64>::from_edge_list(&path, 3, 1.0).is_err());
+}
+
+// A hostile tie costs as much as a friendly one of the same magnitude, so the
+// search over a signed network still finishes.
+#[test]
+fn negative_ties_cost_their_magnitude() {
+    let mut matrix = Matrix::new(3);
+    matrix.put(0, 1, -0.5);
+    matrix.put(1, 2, 1.0);
+
+    assert_eq!(matrix.weighted_shortest_path(0, 2), Some(3.0));
+    // 2, 3 and 1 for the pairs 0-1, 0-2 and 1-2, each counted both ways.
+    assert_close(matrix.average_weighted_path_length(), 2.0);
+
+    let network: Network = matrix.into();
+    assert_close(network.strength(0), 0.5);
+    assert_close(network.strength(1), 1.5);
+}
+
+// Member 0 is tied to 1 at 0.5 and to 2 at full strength, holding 0.2 against
+// 0.4 and 0.9.
+fn star() -> Network {
+    let mut matrix = Matrix::new(3);
+    matrix.put(0, 1, 0.5);
+    matrix.put(0, 2, 1.0);
+    let mut network: Network = matrix.into();
+    network.set_opinions(vec![vec![0.2], vec![0.4], vec![0.9]]);
+    network
+}
+
+fn deffuant() -> DeffuantModel {
+    DeffuantModel { bound: ConfidenceBound(0.3), rate: ConvergenceRate(0.5) }
+}
+
+#[test]
+fn opinions_are_clamped_and_averaged() {
+    let mut network = star();
+    network.set_opinion(1, vec![1.5]);
+    network.set_opinion(2, vec![-0.1]);
+    assert_close(network.mean_opinion()[0], (0.2 + 1.5 - 0.1) / 3.0);
+
+    network.clamp_opinions(Boundary::Clamp, 0.0, 1.0);
+    assert_eq!(network.opinions(), &[vec![0.2], vec![1.0], vec![0.0]][..]);
+    assert_close(network.mean_opinion()[0], 0.4);
+}
+
+// Only member 1 is within the confidence bound of the message, and nothing is
+// applied until the caller does it.
+#[test]
+fn broadcasts_work_out_every_outcome_without_applying_them() {
+    let network = star();
+    let outcomes = network.broadcast(0, &[0.2], &deffuant(), false, None);
+
+    assert_eq!(outcomes.len(), 2);
+    let (recipient, strength, ref outcome) = outcomes[0];
+    assert_eq!((recipient, strength), (1, 0.5));
+    assert_close(outcome.sender[0], 0.3);
+    assert_close(outcome.recipient[0], 0.3);
+    let (recipient, strength, ref outcome) = outcomes[1];
+    assert_eq!((recipient, strength), (2, 1.0));
+    assert_eq!(outcome.sender, vec![0.2]);
+    assert_eq!(outcome.recipient, vec![0.9]);
+    assert_eq!(network.opinions(), star().opinions());
+}
+
+// The reply from member 1 moves the sender a second time, by as much as the
+// message moved member 1.
+#[test]
+fn mutual_broadcasts_move_the_sender_with_every_reply() {
+    let network = star();
+    let pool = ThreadPool::new(Configuration::new().num_threads(2)).unwrap();
+    let outcomes = network.broadcast(0, &[0.2], &deffuant(), true, None);
+    assert_close(outcomes[0].2.sender[0], 0.4);
+    assert_eq!(outcomes[1].2.sender, vec![0.2]);
+    assert_eq!(network.broadcast(0, &[0.2], &deffuant(), true, Some(&pool)), outcomes);
+}
+
+// Join each member to the next one along `members`.
+fn path(members: &[usize], size: usize) -> Matrix<f64> {
+    let mut matrix = Matrix::new(size);
+    for pair in members.windows(2) {
+        matrix.put(pair[0], pair[1], 1.0);
+    }
+    matrix
+}
+
+#[test]
+fn clustering_counts_closed_triplets() {
+    assert_eq!(path(&[0, 1, 2, 0], 3).clustering_coefficient(), 1.0);
+    assert_eq!(path(&[0, 1, 2, 3], 4).clustering_coefficient(), 0.0);
+    // A triangle with a tail: three of the five triplets are closed.
+    assert_close(path(&[0, 1, 2, 0, 3], 4).clustering_coefficient(), 0.6);
+}
+
+// Around a ring of six members the others are 1, 1, 2, 2 and 3 hops away.
+#[test]
+fn ring_path_length_matches_its_circumference() {
+    assert_close(Matrix::<f64>::ring_lattice(6, 2, 1.0).average_path_length(), 1.8);
+    assert_close(Matrix::<f64>::ring_lattice(7, 2, 1.0).average_path_length(), 2.0);
+    // Isolated members are out of reach and do not count.
+    assert_eq!(path(&[0, 1], 4).average_path_length(), 1.0);
+}
+
+// Every non-zero weight in `node`'s row, found by scanning the whole row.
+fn scanned(network: &Network, node: usize) -> Vec<(usize, f64)> {
+    (0..network.size())
+        .map(|other| (other, network.weight(node, other)))
+        .filter(|&(_, weight)| weight != 0.0)
+        .collect()
+}
+
+// The lists kept alongside the storage follow ties as they are cut, formed
+// and merely changed, in every kind of storage.
+#[test]
+fn neighbor_lists_follow_the_storage() {
+    let mut rng: StdRng = SeedableRng::from_seed(&[6][..]);
+    let matrix: Matrix<f64> = Matrix::erdos_renyi(30, 0.2, 1.0, &mut rng);
+    let sparse: SparseMatrix<f64> = SparseMatrix::erdos_renyi(30, 0.2, 1.0, &mut rng);
+    let networks: Vec<Network> = vec![matrix.clone().into(), sparse.into(),
+                                      DirectedMatrix::from(matrix).into()];
+
+    for mut network in networks {
+        for node in 0..30 {
+            assert_eq!(network.neighbors(node), scanned(&network, node));
+        }
+        for node in 0..30 {
+            let first = network.neighbors(node).first().map(|&(neighbor, _)| neighbor);
+            if let Some(neighbor) = first {
+                network.set_weight(node, neighbor, 0.0);
+            }
+            network.set_weight(node, (node + 7) % 30, 0.5);
+            network.set_weight(node, (node + 11) % 30, 0.0);
+        }
+        for node in 0..30 {
+            assert_eq!(network.neighbors(node), scanned(&network, node), "member {}", node);
+        }
+    }
+}
+
+#[test]
+fn density_spans_the_empty_and_complete_graphs() {
+    let empty: Matrix<f64> = Matrix::new(5);
+    assert_eq!((empty.edge_count(), empty.density()), (0, 0.0));
+    let complete: Matrix<f64> = Matrix::complete(5, 1.0);
+    assert_eq!((complete.edge_count(), complete.density()), (10, 1.0));
+    let sparse: SparseMatrix<f64> = SparseMatrix::complete(5, 1.0);
+    assert_eq!((sparse.edge_count(), sparse.density()), (10, 1.0));
+}
+
+// Two triangles with nothing between them: each block holds half the ties and
+// half the strength, for 2 * (1/2 - 1/4). Lumping them together scores 0.
+#[test]
+fn modularity_separates_two_disconnected_blocks() {
+    let mut matrix = path(&[0, 1, 2, 0], 6);
+    for pair in [3, 4, 5, 3].windows(2) {
+        matrix.put(pair[0], pair[1], 1.0);
+    }
+    assert_close(matrix.modularity(&[0, 0, 0, 1, 1, 1]), 0.5);
+    assert_close(matrix.modularity(&[0; 6]), 0.0);
+    assert!(matrix.modularity(&[0, 1, 0, 1, 0, 1]) < 0.0);
+}
+
+// Member 0 sends to 1 and 2, and only 2 sends back.
+#[test]
+fn directed_ties_count_each_direction_separately() {
+    let mut matrix = DirectedMatrix::new(3);
+    matrix.put(0, 1, 0.5);
+    matrix.put(0, 2, 0.5);
+    matrix.put(2, 0, 1.0);
+    let network: Network = matrix.into();
+
+    assert!(network.is_directed());
+    assert_eq!(network.neighbors(0), vec![(1, 0.5), (2, 0.5)]);
+    assert_eq!(network.incoming(0), vec![(2, 1.0)]);
+    let degrees: Vec<(usize, usize)> = (0..3)
+        .map(|node| (network.degree(node), network.incoming(node).len()))
+        .collect();
+    assert_eq!(degrees, vec![(2, 1), (0, 1), (1, 1)]);
+    assert_close(network.strength(0), 1.0);
+    assert_close(network.strength(1), 0.0);
+}
@@ -0,0 +1,137 @@
+extern crate bubbles;
+extern crate rustc_serialize;
+
+use std::collections::BTreeMap;
+use std::env;
+
+use bubbles::{parameters, simulate, write_json, Checkpoint, SimulationConfig, SimulationResult,
+              Topology};
+use rustc_serialize::json::Json;
+
+fn config(seed: u64) -> SimulationConfig {
+    let mut config = SimulationConfig::new(30, 4, 0.1, 20.0, 60.0, seed);
+    config.ticks = 400;
+    config.rewire_on_conflict = true;
+    config
+}
+
+// Stop a run at its checkpoint on tick 350 and carry on from there to the end,
+// returning both parts. Every run gets its own checkpoint file, since tests
+// run in parallel.
+fn interrupted(seed: u64, record_history: bool) -> (SimulationResult, SimulationResult) {
+    let path = env::temp_dir().join(format!("bubbles_resume_{}_{}.txt", seed, record_history));
+    let mut first = config(seed);
+    first.ticks = 350;
+    first.record_history = record_history;
+    first.checkpoint_interval = Some(350);
+    first.checkpoint_path = path.to_string_lossy().into_owned();
+    let before = simulate(&first);
+
+    let mut rest = config(seed);
+    rest.record_history = record_history;
+    rest.resume = Some(Checkpoint::from_file(&first.checkpoint_path)
+        .expect("the checkpoint was not written"));
+    (before, simulate(&rest))
+}
+
+fn resumed(seed: u64) -> SimulationResult {
+    interrupted(seed, false).1
+}
+
+fn json(config: &SimulationConfig, result: &SimulationResult) -> Json {
+    let mut out = Vec::new();
+    write_json(&mut out, config, result).unwrap();
+    Json::from_str(&String::from_utf8(out).unwrap()).unwrap()
+}
+
+#[test]
+fn resuming_from_a_checkpoint_matches_an_uninterrupted_run() {
+    for seed in 1..6 {
+        let whole = simulate(&config(seed));
+        let resumed = resumed(seed);
+
+        assert_eq!(resumed.first_tick, 350);
+        assert_eq!(resumed.opinions, whole.opinions, "seed {}", seed);
+        assert_eq!(resumed.network.edges(), whole.network.edges(), "seed {}", seed);
+        assert_eq!(resumed.converged_at, whole.converged_at, "seed {}", seed);
+        // The oscillation check looks back over ticks from before the
+        // checkpoint.
+        assert_eq!(resumed.oscillating, whole.oscillating, "seed {}", seed);
+    }
+}
+
+// The document of a resumed run starts from the network and opinions at the
+// checkpoint and carries on as the uninterrupted run did.
+#[test]
+fn resumed_json_carries_on_from_the_checkpoint() {
+    let mut config = config(2);
+    config.record_history = true;
+    let whole = json(&config, &simulate(&config));
+    let (before, after) = interrupted(2, true);
+    let before = json(&config, &before);
+    let after = json(&config, &after);
+
+    assert_eq!(after["first_tick"], Json::U64(350));
+    let initial = after["initial_network"].as_array().unwrap();
+    assert!(!initial.is_empty());
+    assert_eq!(initial, before["final_network"].as_array().unwrap());
+    assert_eq!(after["final_network"], whole["final_network"]);
+    assert_eq!(after["opinions"].as_array().unwrap()[..],
+               whole["opinions"].as_array().unwrap()[350..]);
+    assert_eq!(after["converged_at"], whole["converged_at"]);
+    assert_eq!(after["oscillating"], whole["oscillating"]);
+}
+
+// Settings that only shape how a run is recorded and where it carried on from
+// are listed with the rest.
+#[test]
+fn parameters_include_the_checkpoint_settings() {
+    let mut config = config(1);
+    config.record_history = true;
+    config.checkpoint_interval = Some(50);
+    config.checkpoint_path = "run_checkpoint.txt".to_string();
+    config.resume = Some(Checkpoint {
+        tick: 350,
+        draws: 1234,
+        stable_ticks: 0,
+        changes: Vec::new(),
+        directed: false,
+        opinions: Vec::new(),
+        stubbornness: Vec::new(),
+        openness: Vec::new(),
+        positions: None,
+        edges: Vec::new()
+    });
+
+    let parameters: BTreeMap<String, String> = parameters(&config).into_iter().collect();
+    assert_eq!(parameters["record_history"], "true");
+    assert_eq!(parameters["checkpoint_interval"], "50");
+    assert_eq!(parameters["checkpoint_path"], "run_checkpoint.txt");
+    assert_eq!(parameters["resume"], "draws:1234 tick:350");
+    assert_eq!(parameters["network"], "");
+}
+
+// Members of a spatial network keep the places they were drawn at before the
+// checkpoint.
+#[test]
+fn resuming_keeps_the_positions_of_a_spatial_network() {
+    let path = env::temp_dir().join("bubbles_resume_geometric.txt");
+    let mut whole = config(4);
+    whole.topology = Topology::Geometric;
+    whole.radius = 0.3;
+    let mut first = whole.clone();
+    first.ticks = 350;
+    first.checkpoint_interval = Some(350);
+    first.checkpoint_path = path.to_string_lossy().into_owned();
+    simulate(&first);
+
+    let mut rest = whole.clone();
+    rest.resume = Some(Checkpoint::from_file(&first.checkpoint_path)
+        .expect("the checkpoint was not written"));
+    let resumed = simulate(&rest);
+    let whole = simulate(&whole);
+
+    assert!(whole.positions.is_some());
+    assert_eq!(resumed.positions, whole.positions);
+    assert_eq!(resumed.opinions, whole.opinions);
+}
@@ -0,0 +1,30 @@
+extern crate bubbles;
+
+use bubbles::{mean, simulate, variance, SimulationConfig};
+
+#[test]
+fn mean_and_variance_match_hand_computed_values() {
+    assert_eq!(mean(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]), 5.0);
+    assert_eq!(variance(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]), 4.0);
+    assert_eq!(variance(&[3.0]), 0.0);
+    assert_eq!(mean(&[]), 0.0);
+    assert_eq!(variance(&[]), 0.0);
+}
+
+// The per-tick summary is the same statistic taken over every member's
+// opinion in every dimension.
+#[test]
+fn summaries_use_the_shared_statistics() {
+    let mut config = SimulationConfig::new(30, 4, 0.1, 20.0, 60.0, 3);
+    config.ticks = 50;
+    config.dimensions = 2;
+    let result = simulate(&config);
+
+    let values: Vec<f64> = result.opinions.iter().flat_map(|opinion| opinion.clone()).collect();
+    let last = result.summary.last().unwrap();
+    assert_eq!(last.tick, 50);
+    assert_eq!(last.mean, mean(&values));
+    assert_eq!(last.variance, variance(&values));
+    assert_eq!(last.min, values.iter().cloned().fold(std::f64::INFINITY, f64::min));
+    assert_eq!(last.max, values.iter().cloned().fold(std::f64::NEG_INFINITY, f64::max));
+}
@@ -0,0 +1,47 @@
+extern crate bubbles;
+
+use bubbles::{simulate, Model, SimulationConfig, SimulationResult};
+
+fn config(model: Model) -> SimulationConfig {
+    let mut config = SimulationConfig::new(40, 6, 0.1, 20.0, 60.0, 5);
+    config.ticks = 300;
+    config.model = model;
+    config
+}
+
+// Run the same configuration on its own and over a thread pool.
+fn with_and_without_threads(config: &SimulationConfig) -> (SimulationResult, SimulationResult) {
+    let mut threaded = config.clone();
+    threaded.threads = Some(2);
+    (simulate(config), simulate(&threaded))
+}
+
+fn assert_same(single: &SimulationResult, threaded: &SimulationResult) {
+    assert_eq!(threaded.opinions, single.opinions);
+    assert_eq!(threaded.network.edges(), single.network.edges());
+    assert_eq!(threaded.events, single.events);
+}
+
+#[test]
+fn threads_do_not_change_the_run() {
+    for &model in &[Model::Threshold, Model::Deffuant] {
+        for &threads in &[1, 2, 4] {
+            let config = config(model);
+            let mut threaded = config.clone();
+            threaded.threads = Some(threads);
+            assert_same(&simulate(&config), &simulate(&threaded));
+        }
+    }
+}
+
+// A mutual exchange moves the sender once per recipient, which must add up
+// the same whichever order the outcomes were worked out in.
+#[test]
+fn mutual_exchanges_do_not_depend_on_threads() {
+    for &model in &[Model::Threshold, Model::Deffuant] {
+        let mut config = config(model);
+        config.mutual = true;
+        let (single, threaded) = with_and_without_threads(&config);
+        assert_same(&single, &threaded);
+    }
+}